@@ -0,0 +1,29 @@
+//! Recompute the Merkle root of mainnet block 100000 from its txids and prove one transaction.
+//!
+//! `cargo run --example bitcoin_block --no-default-features`
+
+use merkle::{bitcoin, hasher::DoubleSha256, proof};
+
+/// Txids of block 100000 in display order, coinbase first
+const TXIDS: [&str; 4] = [
+    "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+    "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+    "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+    "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+];
+
+fn main() -> Result<(), merkle::error::MerkleError> {
+    let txids = TXIDS.iter().map(|txid| bitcoin::txid_from_hex(txid)).collect::<Result<Vec<_>, _>>()?;
+    let (root, mutated) = bitcoin::merkle_root(&txids)?;
+    println!("merkle root: {}", bitcoin::to_display_hex(&root));
+    println!("mutated: {}", mutated);
+
+    // an SPV client only needs the siblings on the path of its transaction
+    let mut tree = bitcoin::build_tree(&txids)?;
+    let inclusion = tree.inclusion_proof(2)?;
+    for step in &inclusion.steps {
+        println!("sibling on the {:?}: {}", step.side, bitcoin::to_display_hex(&step.sibling));
+    }
+    println!("tx {} included: {}", TXIDS[2], proof::verify(&root, &txids[2], 2, &inclusion, &DoubleSha256)?);
+    Ok(())
+}
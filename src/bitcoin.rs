@@ -0,0 +1,128 @@
+//! Bitcoin's block Merkle root over transaction ids.
+//!
+//! Nodes are combined with double SHA-256, the last node of an odd level is paired with itself,
+//! and txids are hashed in their internal byte order, the reverse of how explorers display them.
+
+use crate::error::MerkleError;
+use crate::hasher::{DoubleSha256, Digest, PairEncoding};
+use crate::tree::{self, LeafOrder, MerkleTree, OddNodeRule, TreeOptions};
+
+/// Tree settings matching Bitcoin Core's `ComputeMerkleRoot`
+pub const OPTIONS: TreeOptions = TreeOptions {
+    odd_rule: OddNodeRule::Duplicate,
+    encoding: PairEncoding::Bytes,
+    domain_separation: false,
+    leaf_order: LeafOrder::AsGiven,
+    sorted_pairs: false,
+};
+
+/// Parse a txid or block hash as displayed by explorers and RPC into its internal byte order
+pub fn txid_from_hex(hex: &str) -> Result<Digest, MerkleError> {
+    let mut digest = Digest::from_hex(hex).ok_or_else(|| MerkleError::InvalidHex(hex.to_string()))?;
+    digest.0.reverse();
+    Ok(digest)
+}
+
+/// Hex of a hash in display order, as explorers show txids and Merkle roots
+pub fn to_display_hex(digest: &Digest) -> String {
+    let mut reversed = *digest;
+    reversed.0.reverse();
+    reversed.to_hex()
+}
+
+/// Build the transaction tree of a block from its txids in internal byte order, coinbase first.
+/// The words of the tree are the txids in display order.
+pub fn build_tree(txids: &[Digest]) -> Result<MerkleTree<DoubleSha256>, MerkleError> {
+    let mut tree = tree::build_tree_from_hashes(txids, DoubleSha256, OPTIONS)?;
+    tree.words = txids.iter().map(to_display_hex).collect();
+    Ok(tree)
+}
+
+/// The Merkle root of a block's txids, and whether the list is mutated (CVE-2012-2459).
+/// A mutated list repeats txids so that it gives the same root as a different, valid list.
+pub fn merkle_root(txids: &[Digest]) -> Result<(Digest, bool), MerkleError> {
+    let tree = build_tree(txids)?;
+    let root = tree.root_hash().ok_or(MerkleError::EmptyTree)?;
+    Ok((root, is_mutated(&tree)))
+}
+
+/// Whether two real siblings anywhere in the tree are equal, which is how Bitcoin Core detects
+/// a txid list padded to collide with the duplicated odd node (CVE-2012-2459)
+pub fn is_mutated<H: crate::hasher::MerkleHasher>(tree: &MerkleTree<H>) -> bool {
+    (1..tree.levels).any(|level| {
+        tree.nodes.get(&level).is_some_and(|nodes| {
+            nodes.chunks(2).any(|pair| pair.len() == 2 && pair[0].hash == pair[1].hash)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof;
+
+    /// Mainnet blocks as (height, merkle root, txids), hashes in display order
+    const BLOCKS: [(u32, &str, &[&str]); 3] = [
+        (0, "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b", &[
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+        ]),
+        (170, "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff", &[
+            "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082",
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+        ]),
+        (100000, "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766", &[
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]),
+    ];
+
+    fn txids(hexes: &[&str]) -> Vec<Digest> {
+        hexes.iter().map(|txid| txid_from_hex(txid).unwrap()).collect()
+    }
+
+    #[test]
+    fn matches_mainnet_block_roots() {
+        for (height, expected, hexes) in BLOCKS {
+            let (root, mutated) = merkle_root(&txids(hexes)).unwrap();
+            assert_eq!(to_display_hex(&root), expected, "block {}", height);
+            assert!(!mutated, "block {}", height);
+        }
+    }
+
+    #[test]
+    fn proves_mainnet_transactions() {
+        let (_, expected, hexes) = BLOCKS[2];
+        let txids = txids(hexes);
+        let mut tree = build_tree(&txids).unwrap();
+        let root = txid_from_hex(expected).unwrap();
+        for (index, txid) in txids.iter().enumerate() {
+            let inclusion = tree.inclusion_proof(index).unwrap();
+            assert_eq!(proof::verify(&root, txid, index, &inclusion, &DoubleSha256), Ok(true));
+        }
+    }
+
+    #[test]
+    fn detects_padded_txid_lists() {
+        let (_, _, hexes) = BLOCKS[2];
+        let mut txids = txids(&hexes[..3]);
+        let (root, mutated) = merkle_root(&txids).unwrap();
+        assert!(!mutated);
+
+        // repeating the last txid of an odd list gives the same root
+        txids.push(txids[2]);
+        let (padded_root, mutated) = merkle_root(&txids).unwrap();
+        assert_eq!(root, padded_root);
+        assert!(mutated);
+    }
+
+    #[test]
+    fn txids_are_shown_reversed() {
+        let hex = BLOCKS[1].2[1];
+        let txid = txid_from_hex(hex).unwrap();
+        assert_eq!(to_display_hex(&txid), hex);
+        assert_ne!(txid.to_hex(), hex);
+        assert_eq!(txid_from_hex("zz"), Err(MerkleError::InvalidHex("zz".to_string())));
+    }
+}
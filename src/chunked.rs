@@ -0,0 +1,304 @@
+//! Trees over the fixed-size chunks of a file, so a single downloaded chunk can be checked against
+//! the root on its own, the way BitTorrent v2 checks pieces and IPFS checks blocks.
+//!
+//! Every chunk but the last is `chunk_size` bytes long. Chunks are hashed with `hasher::hash_leaf`
+//! and stay in file order, and only their hashes are kept, so files are read as a stream.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::MerkleError;
+use crate::hasher::{self, Digest, MerkleHasher};
+use crate::proof::{self, InclusionProof, ProofError};
+use crate::tree::{self, MerkleTree, TreeOptions};
+
+pub const KIB: usize = 1024;
+pub const MIB: usize = 1024 * KIB;
+/// Chunk size used when none is picked, the block size of most file systems
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * KIB;
+
+#[derive(Debug)]
+pub enum ChunkError {
+    Io(io::Error),
+    /// Chunks must hold at least one byte
+    ZeroChunkSize,
+    /// An empty file has no chunks to build a tree over
+    Empty,
+    /// Chunk positions are their leaf indices, so the leaves can't be sorted
+    SortedOrder,
+    /// The file has no chunk at `index`
+    IndexOutOfRange { index: usize, count: usize },
+    Tree(MerkleError),
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkError::Io(error) => write!(f, "reading the file: {}", error),
+            ChunkError::ZeroChunkSize => write!(f, "the chunk size must be at least one byte"),
+            ChunkError::Empty => write!(f, "the file is empty"),
+            ChunkError::SortedOrder => write!(f, "chunks must stay in file order"),
+            ChunkError::IndexOutOfRange { index, count } => write!(f, "chunk {} is out of range for {} chunks", index, count),
+            ChunkError::Tree(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+impl From<io::Error> for ChunkError {
+    fn from(error: io::Error) -> Self {
+        ChunkError::Io(error)
+    }
+}
+
+impl From<MerkleError> for ChunkError {
+    fn from(error: MerkleError) -> Self {
+        ChunkError::Tree(error)
+    }
+}
+
+/// A file split into chunks, with the tree over their hashes.
+/// The words of the tree are the chunk numbers.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ChunkedFile<H: MerkleHasher> {
+    pub chunk_size: usize,
+    pub file_size: u64,
+    pub tree: MerkleTree<H>,
+}
+
+/// Inclusion proof of one chunk, with the sizes needed to tell how long the chunk must be
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChunkProof {
+    pub chunk_size: usize,
+    pub file_size: u64,
+    pub proof: InclusionProof,
+}
+
+impl ChunkProof {
+    pub fn index(&self) -> usize {
+        self.proof.leaf_index
+    }
+}
+
+/// Number of chunks of `chunk_size` bytes in a file of `file_size` bytes
+pub fn chunk_count(file_size: u64, chunk_size: usize) -> usize {
+    file_size.div_ceil(chunk_size as u64) as usize
+}
+
+/// Byte range of the chunk at `index`, `None` past the end of the file
+pub fn chunk_range(index: usize, file_size: u64, chunk_size: usize) -> Option<Range<u64>> {
+    let start = index as u64 * chunk_size as u64;
+    if start >= file_size {
+        return None;
+    }
+    Some(start..file_size.min(start + chunk_size as u64))
+}
+
+impl<H: MerkleHasher> ChunkedFile<H> {
+    pub fn root(&self) -> Option<Digest> {
+        self.tree.root_hash()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.tree.leaf_count()
+    }
+
+    pub fn chunk_range(&self, index: usize) -> Option<Range<u64>> {
+        chunk_range(index, self.file_size, self.chunk_size)
+    }
+
+    /// Proof that the chunk at `index` belongs to the file
+    pub fn proof(&mut self, index: usize) -> Result<ChunkProof, MerkleError> {
+        let proof = self.tree.inclusion_proof(index)?;
+        Ok(ChunkProof { chunk_size: self.chunk_size, file_size: self.file_size, proof })
+    }
+
+    /// A small tree of the top levels, for drawing: its leaves are the nodes of the lowest level
+    /// with at most `max_leaves` nodes, labelled with the chunks under them. Its root is the file's root.
+    pub fn top_levels(&self, max_leaves: usize) -> Result<MerkleTree<H>, MerkleError>
+    where
+        H: Clone,
+    {
+        let level = (1..=self.tree.levels)
+            .find(|level| self.tree.nodes.get(level).is_some_and(|nodes| nodes.len() <= max_leaves.max(1)))
+            .ok_or(MerkleError::MissingLevel(self.tree.levels))?;
+        let nodes = self.tree.nodes.get(&level).ok_or(MerkleError::MissingLevel(level))?;
+
+        let hashes: Vec<Digest> = nodes.iter().map(|node| node.hash).collect();
+        let mut top = tree::build_tree_from_hashes(&hashes, self.tree.hasher.clone(), self.tree.options)?;
+        let count = self.chunk_count();
+        top.words = nodes.iter().map(|node| {
+            let first = node.index << (level - 1);
+            let last = (((node.index + 1) << (level - 1)) - 1).min(count - 1);
+            if first == last { first.to_string() } else { format!("{}-{}", first, last) }
+        }).collect();
+        Ok(top)
+    }
+}
+
+/// Split everything `reader` yields into chunks of `chunk_size` bytes and build the tree over them.
+/// Fails with `ChunkError::Empty` when the reader yields nothing.
+pub fn build_from_reader<R: Read, H: MerkleHasher>(mut reader: R, chunk_size: usize, hasher: H, options: TreeOptions) -> Result<ChunkedFile<H>, ChunkError> {
+    if chunk_size == 0 {
+        return Err(ChunkError::ZeroChunkSize);
+    }
+    if options.leaf_order.is_sorted() {
+        return Err(ChunkError::SortedOrder);
+    }
+
+    let mut hashes = Vec::new();
+    let mut file_size = 0;
+    let mut chunk = Vec::with_capacity(chunk_size);
+    loop {
+        chunk.clear();
+        // `take` keeps reading until the chunk is full, so short reads don't cut chunks early
+        let read = (&mut reader).take(chunk_size as u64).read_to_end(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        file_size += read as u64;
+        hashes.push(hasher::hash_leaf(&hasher, &chunk, &options));
+        if read < chunk_size {
+            break;
+        }
+    }
+
+    if hashes.is_empty() {
+        return Err(ChunkError::Empty);
+    }
+
+    let mut tree = tree::build_tree_from_hashes(&hashes, hasher, options)?;
+    tree.words = (0..hashes.len()).map(|index| index.to_string()).collect();
+    Ok(ChunkedFile { chunk_size, file_size, tree })
+}
+
+/// Build the chunk tree of the file at `path`
+pub fn build_from_file<P: AsRef<Path>, H: MerkleHasher>(path: P, chunk_size: usize, hasher: H, options: TreeOptions) -> Result<ChunkedFile<H>, ChunkError> {
+    build_from_reader(io::BufReader::new(File::open(path)?), chunk_size, hasher, options)
+}
+
+/// Read the chunk at `index` of the file at `path`, e.g. to send it along with its proof
+pub fn read_chunk<P: AsRef<Path>>(path: P, index: usize, chunk_size: usize) -> Result<Vec<u8>, ChunkError> {
+    if chunk_size == 0 {
+        return Err(ChunkError::ZeroChunkSize);
+    }
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let range = chunk_range(index, file_size, chunk_size)
+        .ok_or(ChunkError::IndexOutOfRange { index, count: chunk_count(file_size, chunk_size) })?;
+
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut chunk = Vec::with_capacity((range.end - range.start) as usize);
+    file.take(range.end - range.start).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+/// Check one downloaded `chunk` against the file's `root`, without the rest of the file.
+///
+/// The proof must be for as many chunks as its sizes give, and a chunk of the wrong length
+/// is rejected before hashing. Returns `Ok(false)` when the chunk doesn't belong to the file.
+pub fn verify_chunk<H: MerkleHasher>(root: &Digest, chunk: &[u8], proof: &ChunkProof, hasher: &H) -> Result<bool, ProofError> {
+    if proof.chunk_size == 0 || proof.proof.tree_size != chunk_count(proof.file_size, proof.chunk_size) {
+        return Err(ProofError::MalformedProof);
+    }
+    let range = chunk_range(proof.index(), proof.file_size, proof.chunk_size)
+        .ok_or(ProofError::IndexOutOfRange { index: proof.index(), tree_size: proof.proof.tree_size })?;
+    if chunk.len() as u64 != range.end - range.start {
+        return Ok(false);
+    }
+
+    let leaf = hasher::hash_leaf(hasher, chunk, &proof.proof.options);
+    proof::verify(root, &leaf, proof.index(), &proof.proof, hasher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::Sha256;
+
+    /// 10000 bytes, so the last of the 1024-byte chunks is short
+    fn payload() -> Vec<u8> {
+        (0..10000u32).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn chunked() -> ChunkedFile<Sha256> {
+        build_from_reader(payload().as_slice(), KIB, Sha256, TreeOptions::default()).unwrap()
+    }
+
+    fn chunk(payload: &[u8], index: usize) -> &[u8] {
+        let range = chunk_range(index, payload.len() as u64, KIB).unwrap();
+        &payload[range.start as usize..range.end as usize]
+    }
+
+    #[test]
+    fn every_chunk_verifies() {
+        let payload = payload();
+        let mut file = chunked();
+        let root = file.root().unwrap();
+        assert_eq!(file.file_size, 10000);
+        assert_eq!(file.chunk_count(), 10);
+        assert_eq!(file.chunk_range(9), Some(9216..10000));
+        assert_eq!(file.chunk_range(10), None);
+
+        for index in 0..file.chunk_count() {
+            let proof = file.proof(index).unwrap();
+            assert_eq!(proof.index(), index);
+            assert_eq!(verify_chunk(&root, chunk(&payload, index), &proof, &Sha256), Ok(true), "chunk {}", index);
+        }
+    }
+
+    #[test]
+    fn wrong_chunks_are_rejected() {
+        let payload = payload();
+        let mut file = chunked();
+        let root = file.root().unwrap();
+        let proof = file.proof(3).unwrap();
+
+        let mut flipped = chunk(&payload, 3).to_vec();
+        flipped[100] ^= 1;
+        assert_eq!(verify_chunk(&root, &flipped, &proof, &Sha256), Ok(false));
+
+        assert_eq!(verify_chunk(&root, &chunk(&payload, 3)[1..], &proof, &Sha256), Ok(false));
+        assert_eq!(verify_chunk(&root, chunk(&payload, 4), &proof, &Sha256), Ok(false));
+
+        // the last chunk is short, a full-length one can't stand in for it
+        let last = file.proof(9).unwrap();
+        assert_eq!(verify_chunk(&root, chunk(&payload, 8), &last, &Sha256), Ok(false));
+
+        let mut resized = proof.clone();
+        resized.file_size = 20000;
+        assert_eq!(verify_chunk(&root, chunk(&payload, 3), &resized, &Sha256), Err(ProofError::MalformedProof));
+        resized.chunk_size = 0;
+        assert_eq!(verify_chunk(&root, chunk(&payload, 3), &resized, &Sha256), Err(ProofError::MalformedProof));
+    }
+
+    #[test]
+    fn top_levels_keep_the_root() {
+        let file = chunked();
+        for max_leaves in [1, 2, 3, 4, 8, 16] {
+            let top = file.top_levels(max_leaves).unwrap();
+            assert_eq!(top.root_hash(), file.root(), "at most {} leaves", max_leaves);
+            assert!(top.leaf_count() <= max_leaves);
+        }
+        assert_eq!(file.top_levels(4).unwrap().words, ["0-3", "4-7", "8-9"]);
+        assert_eq!(file.top_levels(16).unwrap().words, (0..10).map(|index| index.to_string()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_files_and_bad_settings_are_rejected() {
+        assert!(matches!(build_from_reader(&[][..], KIB, Sha256, TreeOptions::default()), Err(ChunkError::Empty)));
+        assert!(matches!(build_from_reader(&payload()[..], 0, Sha256, TreeOptions::default()), Err(ChunkError::ZeroChunkSize)));
+        let sorted = TreeOptions { leaf_order: tree::LeafOrder::ByHash, ..Default::default() };
+        assert!(matches!(build_from_reader(&payload()[..], KIB, Sha256, sorted), Err(ChunkError::SortedOrder)));
+    }
+}
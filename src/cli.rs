@@ -0,0 +1,409 @@
+//! `merkle <command>`: the tree logic the GUI shows, for scripts and CI.
+
+use std::io::Read;
+use std::path::Path;
+
+use merkle::{
+    directory::{self, Snapshot},
+    hasher::{self, Digest, HashAlgorithm, MerkleHasher},
+    proof::{self, InclusionProof, ProofStep, Verification},
+    tree::{self, LeafOrder, MerkleTree, OddNodeRule, TreeOptions},
+};
+
+const USAGE: &str = "\
+Usage: merkle <command> [options] [words...]
+
+Commands:
+  build            print the root of the tree over the words
+  prove <index>    print the inclusion proof of the word at <index>
+  verify           check a proof from `prove`, exiting with 0 if it is valid and 1 if not
+  show             draw the tree
+  snapshot <dir>   print the root over the files of <dir>, --out <path> also writes its manifest
+  diff <old> <new> list the files added (A), removed (D) or modified (M) between two directories
+                   or manifests, exiting with 0 if there are none and 1 if there are
+
+The leaves are the words after the command, or the lines of --file <path>, or of stdin.
+A snapshot manifest has one leaf per file, so `build`, `prove` and `verify` work on it as well.
+
+Options:
+  --file <path>        read the leaves from a file, one per line
+  --hash <name>        keccak-256 (default), sha-256, sha-256d, sha3-256, blake2b, blake3
+  --odd <rule>         duplicate (default), promote or pad-empty
+  --order <order>      as-given (default), by-value or by-hash
+  --domain-separation  prefix leaves and nodes as RFC 6962 does
+  --sorted-pairs       hash the children of a node in ascending order
+
+verify options, with the tree options the proof was made with:
+  --root <hex>         root to check against
+  --leaf <word>        the proven word, or --leaf-hash <hex> for its hash
+  --proof <path>       the proof, read from stdin when left out
+
+snapshot options:
+  --out <path>         write the manifest, one `<path> <hash>` line per file
+";
+
+/// Exit codes: the proof checked out, or the command succeeded
+const EXIT_OK: i32 = 0;
+/// The proof doesn't check out
+const EXIT_INVALID: i32 = 1;
+/// Bad arguments or input
+const EXIT_USAGE: i32 = 2;
+
+/// Run the command in `args` (without the program name) and return the exit code
+pub fn run(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprint!("{}", USAGE);
+        return EXIT_USAGE;
+    }
+    match Args::parse(args).and_then(|args| args.run()) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("merkle: {}", message);
+            eprintln!("Run `merkle help` for usage.");
+            EXIT_USAGE
+        }
+    }
+}
+
+#[derive(Default)]
+struct Args {
+    command: String,
+    words: Vec<String>,
+    file: Option<String>,
+    hasher: HashAlgorithm,
+    options: TreeOptions,
+    root: Option<String>,
+    leaf: Option<String>,
+    leaf_hash: Option<String>,
+    proof: Option<String>,
+    out: Option<String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.iter();
+        parsed.command = args.next().cloned().unwrap_or_default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--file" => parsed.file = Some(value()?),
+                "--hash" => parsed.hasher = parse_hasher(&value()?)?,
+                "--odd" => parsed.options.odd_rule = match value()?.as_str() {
+                    "duplicate" => OddNodeRule::Duplicate,
+                    "promote" => OddNodeRule::Promote,
+                    "pad-empty" => OddNodeRule::PadEmpty,
+                    other => return Err(format!("unknown odd node rule {}", other)),
+                },
+                "--order" => parsed.options.leaf_order = match value()?.as_str() {
+                    "as-given" => LeafOrder::AsGiven,
+                    "by-value" => LeafOrder::ByValue,
+                    "by-hash" => LeafOrder::ByHash,
+                    other => return Err(format!("unknown leaf order {}", other)),
+                },
+                "--domain-separation" => parsed.options.domain_separation = true,
+                "--sorted-pairs" => parsed.options.sorted_pairs = true,
+                "--root" => parsed.root = Some(value()?),
+                "--leaf" => parsed.leaf = Some(value()?),
+                "--leaf-hash" => parsed.leaf_hash = Some(value()?),
+                "--proof" => parsed.proof = Some(value()?),
+                "--out" => parsed.out = Some(value()?),
+                flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+                word => parsed.words.push(word.to_string()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn run(mut self) -> Result<i32, String> {
+        match self.command.as_str() {
+            "build" => {
+                let tree = self.build()?;
+                println!("{}", root_hash(&tree)?);
+                Ok(EXIT_OK)
+            }
+            "prove" => {
+                let index = if self.words.is_empty() { None } else { Some(self.words.remove(0)) };
+                let index: usize = index.as_deref().and_then(|index| index.parse().ok())
+                    .ok_or("prove needs the index of the word to prove")?;
+                let mut tree = self.build()?;
+                let proof = tree.inclusion_proof(index).map_err(|error| error.to_string())?;
+                print!("{}", format_proof(&proof));
+                Ok(EXIT_OK)
+            }
+            "verify" => self.verify(),
+            "show" => {
+                let tree = self.build()?;
+                print!("{}", draw(&tree));
+                Ok(EXIT_OK)
+            }
+            "snapshot" => {
+                let [dir] = self.words.as_slice() else {
+                    return Err("snapshot needs one directory".to_string());
+                };
+                let snapshot = self.snapshot(dir)?;
+                if let Some(out) = &self.out {
+                    std::fs::write(out, snapshot.manifest()).map_err(|error| format!("{}: {}", out, error))?;
+                }
+                println!("{}", root_hash(&snapshot.tree)?);
+                Ok(EXIT_OK)
+            }
+            "diff" => {
+                let [old, new] = self.words.as_slice() else {
+                    return Err("diff needs two directories or manifests".to_string());
+                };
+                let changes = self.snapshot(old)?.diff(&self.snapshot(new)?).map_err(|error| error.to_string())?;
+                for change in &changes {
+                    println!("{}", change);
+                }
+                Ok(if changes.is_empty() { EXIT_OK } else { EXIT_INVALID })
+            }
+            "help" | "--help" | "-h" => {
+                print!("{}", USAGE);
+                Ok(EXIT_OK)
+            }
+            other => Err(format!("unknown command {}", other)),
+        }
+    }
+
+    /// Build the tree over the leaves from the arguments, the file or stdin
+    fn build(&self) -> Result<MerkleTree<HashAlgorithm>, String> {
+        let leaves = if !self.words.is_empty() {
+            self.words.clone()
+        } else {
+            let text = match &self.file {
+                Some(path) => std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?,
+                None => read_stdin()?,
+            };
+            text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect()
+        };
+        tree::build_tree_with(leaves, self.hasher, self.options).map_err(|error| error.to_string())
+    }
+
+    /// The snapshot of a directory, or of a manifest written by `snapshot --out`
+    fn snapshot(&self, path: &str) -> Result<Snapshot<HashAlgorithm>, String> {
+        let snapshot = if Path::new(path).is_dir() {
+            directory::snapshot(Path::new(path), self.hasher, self.options)
+        } else {
+            let manifest = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+            directory::from_manifest(&manifest, self.hasher, self.options)
+        };
+        snapshot.map_err(|error| format!("{}: {}", path, error))
+    }
+
+    fn verify(&self) -> Result<i32, String> {
+        let root = parse_digest(self.root.as_deref().ok_or("verify needs --root")?)?;
+        let leaf = match (&self.leaf, &self.leaf_hash) {
+            (Some(word), None) => hasher::hash_word(&self.hasher, word, &self.options),
+            (None, Some(hash)) => parse_digest(hash)?,
+            _ => return Err("verify needs one of --leaf and --leaf-hash".to_string()),
+        };
+        let text = match &self.proof {
+            Some(path) => std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?,
+            None => read_stdin()?,
+        };
+
+        let outcome = match parse_proof(&text, root, self.options)? {
+            Some(proof) => proof::check(&root, &leaf, proof.leaf_index, &proof, &self.hasher),
+            None => Verification::MalformedProof,
+        };
+        println!("{}", outcome.reason());
+        Ok(if outcome.is_valid() { EXIT_OK } else { EXIT_INVALID })
+    }
+}
+
+fn parse_hasher(name: &str) -> Result<HashAlgorithm, String> {
+    let normalize = |name: &str| name.to_ascii_lowercase().replace(['-', ' ', '(', ')'], "");
+    HashAlgorithm::ALL.into_iter()
+        .find(|algorithm| normalize(algorithm.name()) == normalize(name))
+        .ok_or_else(|| format!("unknown hash {}", name))
+}
+
+fn parse_digest(hex: &str) -> Result<Digest, String> {
+    Digest::from_hex(hex).ok_or_else(|| format!("{} is not a 32-byte hex hash", hex))
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).map_err(|error| format!("stdin: {}", error))?;
+    Ok(text)
+}
+
+fn root_hash<H: MerkleHasher>(tree: &MerkleTree<H>) -> Result<Digest, String> {
+    tree.root_hash().ok_or_else(|| "the tree has no root".to_string())
+}
+
+/// The leaf's index, the tree size and the siblings from the leaf up, one per line.
+/// Sides and levels follow from the index and size, so they are left out.
+fn format_proof(proof: &InclusionProof) -> String {
+    let mut text = format!("index {}\nsize {}\n", proof.leaf_index, proof.tree_size);
+    for step in &proof.steps {
+        text.push_str(&format!("sibling {}\n", step.sibling));
+    }
+    text
+}
+
+/// Read a proof written by `format_proof`, for a tree built with `options`.
+/// `None` when it has the wrong number of siblings for its index and size.
+fn parse_proof(text: &str, root: Digest, options: TreeOptions) -> Result<Option<InclusionProof>, String> {
+    let (mut index, mut size, mut siblings) = (None, None, Vec::new());
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = line.trim().split_once(' ').ok_or_else(|| format!("bad proof line {}", line))?;
+        match key {
+            "index" => index = value.parse::<usize>().ok(),
+            "size" => size = value.parse::<usize>().ok(),
+            "sibling" => siblings.push(parse_digest(value)?),
+            _ => return Err(format!("bad proof line {}", line)),
+        }
+    }
+    let (leaf_index, tree_size) = index.zip(size).ok_or("the proof needs an index and a size")?;
+
+    let shape = proof::path_shape(leaf_index, tree_size, options.odd_rule);
+    if shape.len() != siblings.len() {
+        return Ok(None);
+    }
+    let steps = shape.into_iter().zip(siblings)
+        .map(|((level, side), sibling)| ProofStep { sibling, side, level })
+        .collect();
+    Ok(Some(InclusionProof { leaf_index, tree_size, root, steps, options, ..Default::default() }))
+}
+
+/// The tree from the root down, with the word under each leaf
+fn draw<H: MerkleHasher>(tree: &MerkleTree<H>) -> String {
+    let mut text = String::new();
+    if tree.levels > 0 {
+        draw_node(tree, tree.levels, 0, "", "", &mut text);
+    }
+    text
+}
+
+fn draw_node<H: MerkleHasher>(tree: &MerkleTree<H>, level: u32, index: usize, branch: &str, indent: &str, text: &mut String) {
+    let Some(node) = tree.nodes.get(&level).and_then(|nodes| nodes.get(index)) else {
+        return;
+    };
+    let hash = MerkleTree::format_hash(&node.hash.to_hex(), 8, 8, "..");
+    if level == 1 {
+        let word = tree.words.get(index).map(String::as_str).unwrap_or_default();
+        text.push_str(&format!("{}{} {:?}\n", branch, hash, word));
+        return;
+    }
+    text.push_str(&format!("{}{}\n", branch, hash));
+
+    let below = tree.nodes.get(&(level - 1)).map(|nodes| nodes.len()).unwrap_or_default();
+    let children: Vec<usize> = [2 * index, 2 * index + 1].into_iter().filter(|child| *child < below).collect();
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (child_branch, child_indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        draw_node(tree, level - 1, *child, &format!("{}{}", indent, child_branch), &format!("{}{}", indent, child_indent), text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn tree(options: TreeOptions) -> MerkleTree<HashAlgorithm> {
+        tree::build_tree_with(["a", "b", "c", "d", "e"], HashAlgorithm::Sha256, options).unwrap()
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let parsed = Args::parse(&args(&["prove", "2", "--hash", "SHA-256", "--odd", "promote", "--order", "by-hash",
+            "--domain-separation", "--sorted-pairs", "a", "b"])).unwrap();
+        assert_eq!(parsed.command, "prove");
+        assert_eq!(parsed.words, ["2", "a", "b"]);
+        assert_eq!(parsed.hasher, HashAlgorithm::Sha256);
+        assert_eq!(parsed.options, TreeOptions {
+            odd_rule: OddNodeRule::Promote,
+            leaf_order: LeafOrder::ByHash,
+            domain_separation: true,
+            sorted_pairs: true,
+            ..Default::default()
+        });
+        assert_eq!(Args::parse(&args(&["build", "--hash", "poseidon (test)"])).unwrap().hasher, HashAlgorithm::PoseidonTest);
+    }
+
+    #[test]
+    fn bad_arguments_are_usage_errors() {
+        for bad in [
+            &["build", "--wat"][..],
+            &["build", "--hash"],
+            &["build", "--hash", "md5"],
+            &["build", "--odd", "drop"],
+            &["build", "--order", "random"],
+        ] {
+            assert!(Args::parse(&args(bad)).is_err(), "{:?}", bad);
+            assert_eq!(run(&args(bad)), EXIT_USAGE, "{:?}", bad);
+        }
+        assert_eq!(run(&[]), EXIT_USAGE);
+        assert_eq!(run(&args(&["frobnicate", "a"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["prove", "x", "a", "b"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["prove", "5", "a", "b"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["snapshot"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["verify", "--leaf", "a"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["build", "a", "b"])), EXIT_OK);
+    }
+
+    #[test]
+    fn proofs_round_trip_through_text() {
+        for odd_rule in [OddNodeRule::Duplicate, OddNodeRule::Promote, OddNodeRule::PadEmpty] {
+            let options = TreeOptions { odd_rule, ..Default::default() };
+            let mut tree = tree(options);
+            let root = tree.root_hash().unwrap();
+            for index in 0..5 {
+                let proof = tree.inclusion_proof(index).unwrap();
+                let parsed = parse_proof(&format_proof(&proof), root, options).unwrap().unwrap();
+                assert_eq!(parsed.leaf_index, index);
+                assert_eq!(parsed.tree_size, 5);
+                assert_eq!(parsed.steps, proof.steps, "{:?} leaf {}", odd_rule, index);
+                assert_eq!(proof::verify(&root, &tree.nodes[&1][index].hash, index, &parsed, &HashAlgorithm::Sha256), Ok(true));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_proof_text_is_rejected() {
+        let options = TreeOptions::default();
+        let mut tree = tree(options);
+        let root = tree.root_hash().unwrap();
+        let text = format_proof(&tree.inclusion_proof(1).unwrap());
+
+        assert!(parse_proof(&format!("{}leaf 0\n", text), root, options).is_err());
+        assert!(parse_proof(&format!("{}index\n", text), root, options).is_err());
+        assert!(parse_proof(&text.replacen("sibling ", "sibling zz", 1), root, options).is_err());
+        assert!(parse_proof(&text.replacen("sibling ", "sibling 00", 1), root, options).is_err());
+        assert!(parse_proof(&text.replace("size 5\n", ""), root, options).is_err());
+        assert!(parse_proof(&text.replace("index 1\n", "index one\n"), root, options).is_err());
+
+        // a sibling too many or too few is a proof that can't be checked, not a usage error
+        let extra = format!("{}sibling {}\n", text, root);
+        assert!(parse_proof(&extra, root, options).unwrap().is_none());
+        let (short, _) = text.trim_end().rsplit_once('\n').unwrap();
+        assert!(parse_proof(short, root, options).unwrap().is_none());
+    }
+
+    #[test]
+    fn verify_exits_with_the_outcome() {
+        let mut tree = tree(TreeOptions::default());
+        let root = tree.root_hash().unwrap().to_hex();
+        let path = std::env::temp_dir().join(format!("merkle-cli-proof-{}", std::process::id()));
+        let proof = path.to_str().unwrap();
+        let verify = |leaf: &str| run(&args(&["verify", "--hash", "sha-256", "--root", &root, "--leaf", leaf, "--proof", proof]));
+
+        std::fs::write(&path, format_proof(&tree.inclusion_proof(2).unwrap())).unwrap();
+        assert_eq!(verify("c"), EXIT_OK);
+        assert_eq!(verify("x"), EXIT_INVALID);
+
+        std::fs::write(&path, "index 2\nsize 5\n").unwrap();
+        assert_eq!(verify("c"), EXIT_INVALID);
+
+        std::fs::write(&path, "index 2\nsize 5\nsibling 1234\n").unwrap();
+        assert_eq!(verify("c"), EXIT_USAGE);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,714 @@
+//! Saving trees and proofs: JSON for people to read and a compact binary encoding for the wire.
+//!
+//! Both carry `VERSION`. A binary document starts with `MAGIC`, the version and a byte for what it
+//! holds. After that, integers are big-endian, hashes are their 32 bytes, and strings, byte strings
+//! and lists are prefixed with their length as a `u32`. Loading checks everything a document
+//! claims: a tree is rebuilt from its leaves and must give the saved root, and a proof must have
+//! the steps its indices call for.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::MerkleError;
+use crate::hasher::{self, Digest, MerkleHasher, PairEncoding};
+use crate::proof::{self, InclusionProof, MultiProof, ProofError, ProofStep, Side};
+use crate::tree::{self, LeafOrder, MerkleTree, OddNodeRule, TreeOptions};
+
+/// Version of the formats written, the only one read
+pub const VERSION: u8 = 1;
+
+/// First bytes of every binary document
+pub const MAGIC: [u8; 4] = *b"MRKL";
+
+/// What a binary document holds, the byte after the version
+const TREE: u8 = 1;
+const PROOF: u8 = 2;
+const MULTI_PROOF: u8 = 3;
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum CodecError {
+    /// Not JSON of the expected shape
+    Json(String),
+    UnsupportedVersion(u8),
+    /// The bytes don't start with `MAGIC`
+    NotADocument,
+    /// The document holds something else, such as a proof where a tree was expected
+    WrongKind { expected: u8, found: u8 },
+    /// The bytes end inside a value
+    UnexpectedEnd,
+    /// Bytes are left over after the document
+    TrailingBytes,
+    /// An enum or boolean byte out of range
+    InvalidTag(u8),
+    InvalidUtf8,
+    /// A length that doesn't fit this platform's `usize`
+    TooLarge(u64),
+    /// The tree was saved with another hash function
+    HasherMismatch { expected: String, found: String },
+    /// A leaf's hash is not the hash of its value
+    LeafMismatch { index: usize },
+    /// The leaf at `index` sorts before the one ahead of it in a tree built with a sorted `LeafOrder`
+    Unsorted { index: usize },
+    /// The rebuilt tree doesn't have the saved root
+    RootMismatch { expected: Digest, found: Digest },
+    Tree(MerkleError),
+    Proof(ProofError),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Json(error) => write!(f, "invalid json: {}", error),
+            CodecError::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+            CodecError::NotADocument => write!(f, "not a merkle document"),
+            CodecError::WrongKind { expected, found } => write!(f, "expected document kind {}, found {}", expected, found),
+            CodecError::UnexpectedEnd => write!(f, "the document ends early"),
+            CodecError::TrailingBytes => write!(f, "the document has trailing bytes"),
+            CodecError::InvalidTag(tag) => write!(f, "invalid tag {}", tag),
+            CodecError::InvalidUtf8 => write!(f, "a string is not valid utf-8"),
+            CodecError::TooLarge(length) => write!(f, "length {} is too large", length),
+            CodecError::HasherMismatch { expected, found } => write!(f, "the tree was saved with {}, not {}", found, expected),
+            CodecError::LeafMismatch { index } => write!(f, "leaf {} doesn't match its value", index),
+            CodecError::Unsorted { index } => write!(f, "leaf {} is out of order for a sorted tree", index),
+            CodecError::RootMismatch { expected, found } => write!(f, "root mismatch: expected {}, rebuilt {}", expected, found),
+            CodecError::Tree(error) => write!(f, "{}", error),
+            CodecError::Proof(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<MerkleError> for CodecError {
+    fn from(error: MerkleError) -> Self {
+        CodecError::Tree(error)
+    }
+}
+
+impl From<ProofError> for CodecError {
+    fn from(error: ProofError) -> Self {
+        CodecError::Proof(error)
+    }
+}
+
+/// A saved tree: its leaves in tree order, the settings it was built with and its root
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TreeDocument {
+    pub version: u8,
+    /// `MerkleHasher::name` of the tree's hasher
+    pub hasher: String,
+    pub options: TreeOptions,
+    pub leaves: Vec<LeafDocument>,
+    pub root: Digest,
+}
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LeafDocument {
+    pub label: String,
+    #[serde(with = "hex_bytes")]
+    pub value: Vec<u8>,
+    pub hash: Digest,
+    /// The leaf was given as a hash, as by `build_tree_from_hashes`, so `value` is `hash` itself
+    pub prehashed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProofDocument {
+    version: u8,
+    proof: InclusionProof,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MultiProofDocument {
+    version: u8,
+    multi_proof: MultiProof,
+}
+
+/// Just the version, read before the rest of a JSON document
+#[derive(Deserialize)]
+struct Versioned {
+    version: u8,
+}
+
+impl TreeDocument {
+    pub fn from_tree<H: MerkleHasher>(tree: &MerkleTree<H>) -> TreeDocument {
+        let hashes: Vec<Digest> = tree.nodes.get(&1).map(|leaves| leaves.iter().map(|leaf| leaf.hash).collect()).unwrap_or_default();
+        TreeDocument {
+            version: VERSION,
+            hasher: tree.hasher.name().to_string(),
+            options: tree.options,
+            leaves: hashes.into_iter().enumerate().map(|(index, hash)| {
+                let value = tree.values.get(index).cloned().unwrap_or_default();
+                // leaves built from hashes keep the hash as their value, and it doesn't hash to itself
+                let prehashed = value == hash.as_bytes() && hasher::hash_leaf(&tree.hasher, &value, &tree.options) != hash;
+                LeafDocument { label: tree.words.get(index).cloned().unwrap_or_default(), value, hash, prehashed }
+            }).collect(),
+            root: tree.root_hash().unwrap_or_default(),
+        }
+    }
+
+    /// Rebuild the tree with `hasher`, which must be the one it was saved with. Every leaf hash must
+    /// be the hash of its value, or the value itself for leaves marked prehashed, or the empty hash of
+    /// a removed leaf. Leaves of a sorted tree must be in order, and the rebuilt root must be the saved one.
+    pub fn into_tree<H: MerkleHasher>(self, hasher: H) -> Result<MerkleTree<H>, CodecError> {
+        if hasher.name() != self.hasher {
+            return Err(CodecError::HasherMismatch { expected: hasher.name().to_string(), found: self.hasher });
+        }
+
+        for (index, leaf) in self.leaves.iter().enumerate() {
+            let matches = if leaf.prehashed {
+                leaf.value == leaf.hash.as_bytes()
+            } else {
+                let tombstone = leaf.value.is_empty() && leaf.hash == hasher::EMPTY_HASH;
+                tombstone || hasher::hash_leaf(&hasher, &leaf.value, &self.options) == leaf.hash
+            };
+            if !matches {
+                return Err(CodecError::LeafMismatch { index });
+            }
+        }
+
+        // exclusion proofs rely on the order, removed leaves are holes in it
+        let order = self.options.leaf_order;
+        if order.is_sorted() {
+            let keys: Vec<(usize, &[u8])> = self.leaves.iter().enumerate()
+                .filter(|(_, leaf)| !(leaf.value.is_empty() && leaf.hash == hasher::EMPTY_HASH))
+                .map(|(index, leaf)| (index, order.sort_key(&leaf.value, &leaf.hash)))
+                .collect();
+            if let Some(pair) = keys.windows(2).find(|pair| pair[0].1 > pair[1].1) {
+                return Err(CodecError::Unsorted { index: pair[1].0 });
+            }
+        }
+
+        let hashes: Vec<Digest> = self.leaves.iter().map(|leaf| leaf.hash).collect();
+        let (words, values) = self.leaves.into_iter().map(|leaf| (leaf.label, leaf.value)).unzip();
+        let tree = tree::restore_tree(&hashes, values, words, hasher, self.options)?;
+        let root = tree.root_hash().ok_or(MerkleError::EmptyTree)?;
+        if root != self.root {
+            return Err(CodecError::RootMismatch { expected: self.root, found: root });
+        }
+        Ok(tree)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a tree document always serializes")
+    }
+
+    pub fn from_json(json: &str) -> Result<TreeDocument, CodecError> {
+        from_json(json)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(TREE);
+        writer.string(&self.hasher);
+        writer.options(&self.options);
+        writer.u32(self.leaves.len());
+        for leaf in &self.leaves {
+            writer.string(&leaf.label);
+            writer.bytes(&leaf.value);
+            writer.digest(&leaf.hash);
+            writer.flag(leaf.prehashed);
+        }
+        writer.digest(&self.root);
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TreeDocument, CodecError> {
+        let mut reader = Reader::new(bytes, TREE)?;
+        let hasher = reader.string()?;
+        let options = reader.options()?;
+        let mut leaves = Vec::new();
+        for _ in 0..reader.u32()? {
+            leaves.push(LeafDocument { label: reader.string()?, value: reader.bytes()?.to_vec(), hash: reader.digest()?, prehashed: reader.flag()? });
+        }
+        let root = reader.digest()?;
+        reader.finish()?;
+        Ok(TreeDocument { version: VERSION, hasher, options, leaves, root })
+    }
+}
+
+/// `tree` as a JSON `TreeDocument`
+pub fn tree_to_json<H: MerkleHasher>(tree: &MerkleTree<H>) -> String {
+    TreeDocument::from_tree(tree).to_json()
+}
+
+/// Load a tree saved with `tree_to_json`, checking it against `hasher`
+pub fn tree_from_json<H: MerkleHasher>(json: &str, hasher: H) -> Result<MerkleTree<H>, CodecError> {
+    TreeDocument::from_json(json)?.into_tree(hasher)
+}
+
+pub fn tree_to_bytes<H: MerkleHasher>(tree: &MerkleTree<H>) -> Vec<u8> {
+    TreeDocument::from_tree(tree).to_bytes()
+}
+
+/// Load a tree saved with `tree_to_bytes`, checking it against `hasher`
+pub fn tree_from_bytes<H: MerkleHasher>(bytes: &[u8], hasher: H) -> Result<MerkleTree<H>, CodecError> {
+    TreeDocument::from_bytes(bytes)?.into_tree(hasher)
+}
+
+pub fn proof_to_json(proof: &InclusionProof) -> String {
+    let document = ProofDocument { version: VERSION, proof: proof.clone() };
+    serde_json::to_string_pretty(&document).expect("a proof always serializes")
+}
+
+/// Load a proof saved with `proof_to_json`. Its steps must be the ones its index and tree size call for.
+pub fn proof_from_json(json: &str) -> Result<InclusionProof, CodecError> {
+    let document: ProofDocument = from_json(json)?;
+    proof::check_shape(&document.proof)?;
+    Ok(document.proof)
+}
+
+pub fn proof_to_bytes(proof: &InclusionProof) -> Vec<u8> {
+    let mut writer = Writer::new(PROOF);
+    writer.options(&proof.options);
+    writer.u64(proof.leaf_index);
+    writer.u64(proof.tree_size);
+    writer.digest(&proof.root);
+    writer.u32(proof.steps.len());
+    for step in &proof.steps {
+        writer.digest(&step.sibling);
+        writer.u8(match step.side {
+            Side::Left => 0,
+            Side::Right => 1,
+        });
+        writer.u32(step.level as usize);
+    }
+    writer.0
+}
+
+/// Load a proof saved with `proof_to_bytes`. Its steps must be the ones its index and tree size call for.
+pub fn proof_from_bytes(bytes: &[u8]) -> Result<InclusionProof, CodecError> {
+    let mut reader = Reader::new(bytes, PROOF)?;
+    let options = reader.options()?;
+    let leaf_index = reader.u64()?;
+    let tree_size = reader.u64()?;
+    let root = reader.digest()?;
+    let mut steps = Vec::new();
+    for _ in 0..reader.u32()? {
+        let sibling = reader.digest()?;
+        let side = match reader.u8()? {
+            0 => Side::Left,
+            1 => Side::Right,
+            tag => return Err(CodecError::InvalidTag(tag)),
+        };
+        steps.push(ProofStep { sibling, side, level: reader.u32()? as u32 });
+    }
+    reader.finish()?;
+
+    let proof = InclusionProof { leaf_index, tree_size, root, steps, options, ..Default::default() };
+    proof::check_shape(&proof)?;
+    Ok(proof)
+}
+
+pub fn multi_proof_to_json(proof: &MultiProof) -> String {
+    let document = MultiProofDocument { version: VERSION, multi_proof: proof.clone() };
+    serde_json::to_string_pretty(&document).expect("a multiproof always serializes")
+}
+
+/// Load a multiproof saved with `multi_proof_to_json`. Its flags and hashes must be the ones its
+/// leaf indices call for.
+pub fn multi_proof_from_json(json: &str) -> Result<MultiProof, CodecError> {
+    let document: MultiProofDocument = from_json(json)?;
+    proof::check_multi_shape(&document.multi_proof)?;
+    Ok(document.multi_proof)
+}
+
+pub fn multi_proof_to_bytes(proof: &MultiProof) -> Vec<u8> {
+    let mut writer = Writer::new(MULTI_PROOF);
+    writer.options(&proof.options);
+    writer.u64(proof.tree_size);
+    writer.digest(&proof.root);
+    writer.u32(proof.leaf_indices.len());
+    for index in &proof.leaf_indices {
+        writer.u64(*index);
+    }
+    writer.u32(proof.proof.len());
+    for hash in &proof.proof {
+        writer.digest(hash);
+    }
+    writer.u32(proof.proof_flags.len());
+    for flag in &proof.proof_flags {
+        writer.flag(*flag);
+    }
+    writer.0
+}
+
+/// Load a multiproof saved with `multi_proof_to_bytes`. Its flags and hashes must be the ones its
+/// leaf indices call for.
+pub fn multi_proof_from_bytes(bytes: &[u8]) -> Result<MultiProof, CodecError> {
+    let mut reader = Reader::new(bytes, MULTI_PROOF)?;
+    let options = reader.options()?;
+    let tree_size = reader.u64()?;
+    let root = reader.digest()?;
+    let mut leaf_indices = Vec::new();
+    for _ in 0..reader.u32()? {
+        leaf_indices.push(reader.u64()?);
+    }
+    let mut hashes = Vec::new();
+    for _ in 0..reader.u32()? {
+        hashes.push(reader.digest()?);
+    }
+    let mut proof_flags = Vec::new();
+    for _ in 0..reader.u32()? {
+        proof_flags.push(reader.flag()?);
+    }
+    reader.finish()?;
+
+    let proof = MultiProof { leaf_indices, tree_size, root, proof: hashes, proof_flags, options, ..Default::default() };
+    proof::check_multi_shape(&proof)?;
+    Ok(proof)
+}
+
+/// Parse a JSON document after checking its version
+fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, CodecError> {
+    let versioned: Versioned = serde_json::from_str(json).map_err(|error| CodecError::Json(error.to_string()))?;
+    if versioned.version != VERSION {
+        return Err(CodecError::UnsupportedVersion(versioned.version));
+    }
+    serde_json::from_str(json).map_err(|error| CodecError::Json(error.to_string()))
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn new(kind: u8) -> Writer {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(kind);
+        Writer(bytes)
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn flag(&mut self, value: bool) {
+        self.0.push(value as u8);
+    }
+
+    fn u32(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("lengths fit in a u32");
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: usize) {
+        self.0.extend_from_slice(&(value as u64).to_be_bytes());
+    }
+
+    fn digest(&mut self, digest: &Digest) {
+        self.0.extend_from_slice(digest.as_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, text: &str) {
+        self.bytes(text.as_bytes());
+    }
+
+    fn options(&mut self, options: &TreeOptions) {
+        self.u8(match options.odd_rule {
+            OddNodeRule::Duplicate => 0,
+            OddNodeRule::Promote => 1,
+            OddNodeRule::PadEmpty => 2,
+        });
+        self.u8(match options.encoding {
+            PairEncoding::Bytes => 0,
+            PairEncoding::LegacyHex => 1,
+        });
+        self.flag(options.domain_separation);
+        self.u8(match options.leaf_order {
+            LeafOrder::AsGiven => 0,
+            LeafOrder::ByHash => 1,
+            LeafOrder::ByValue => 2,
+        });
+        self.flag(options.sorted_pairs);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Check the header and position the reader after it
+    fn new(data: &'a [u8], kind: u8) -> Result<Reader<'a>, CodecError> {
+        let mut reader = Reader { data };
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(CodecError::NotADocument);
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(CodecError::UnsupportedVersion(version));
+        }
+        let found = reader.u8()?;
+        if found != kind {
+            return Err(CodecError::WrongKind { expected: kind, found });
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], CodecError> {
+        if self.data.len() < length {
+            return Err(CodecError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, CodecError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn flag(&mut self) -> Result<bool, CodecError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(CodecError::InvalidTag(tag)),
+        }
+    }
+
+    fn u32(&mut self) -> Result<usize, CodecError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn u64(&mut self) -> Result<usize, CodecError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        let value = u64::from_be_bytes(bytes);
+        usize::try_from(value).map_err(|_| CodecError::TooLarge(value))
+    }
+
+    fn digest(&mut self) -> Result<Digest, CodecError> {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(self.take(32)?);
+        Ok(Digest(bytes))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], CodecError> {
+        let length = self.u32()?;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<String, CodecError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| CodecError::InvalidUtf8)
+    }
+
+    fn options(&mut self) -> Result<TreeOptions, CodecError> {
+        let odd_rule = match self.u8()? {
+            0 => OddNodeRule::Duplicate,
+            1 => OddNodeRule::Promote,
+            2 => OddNodeRule::PadEmpty,
+            tag => return Err(CodecError::InvalidTag(tag)),
+        };
+        let encoding = match self.u8()? {
+            0 => PairEncoding::Bytes,
+            1 => PairEncoding::LegacyHex,
+            tag => return Err(CodecError::InvalidTag(tag)),
+        };
+        let domain_separation = self.flag()?;
+        let leaf_order = match self.u8()? {
+            0 => LeafOrder::AsGiven,
+            1 => LeafOrder::ByHash,
+            2 => LeafOrder::ByValue,
+            tag => return Err(CodecError::InvalidTag(tag)),
+        };
+        let sorted_pairs = self.flag()?;
+        Ok(TreeOptions { odd_rule, encoding, domain_separation, leaf_order, sorted_pairs })
+    }
+
+    /// Fail if anything is left after the document
+    fn finish(self) -> Result<(), CodecError> {
+        if !self.data.is_empty() {
+            return Err(CodecError::TrailingBytes);
+        }
+        Ok(())
+    }
+}
+
+/// Byte strings as hex in JSON
+mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::hasher::to_hex(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(de::Error::custom(format!("{} is not hex", hex)));
+        }
+        (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| de::Error::custom(format!("{} is not hex", hex))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::{Keccak256, Sha256};
+    use crate::tree::RemovePolicy;
+
+    fn tree() -> MerkleTree<Keccak256> {
+        let mut tree = tree::build_tree(["a", "b", "c", "d", "e"]).unwrap();
+        tree.remove(2, RemovePolicy::Tombstone).unwrap();
+        tree
+    }
+
+    fn sorted_tree() -> MerkleTree<Keccak256> {
+        let options = TreeOptions { leaf_order: LeafOrder::ByHash, ..Default::default() };
+        tree::build_tree_with(["a", "b", "c", "d"], Keccak256, options).unwrap()
+    }
+
+    /// Save `tree` both ways and check that loading gives the same tree back
+    fn assert_round_trip(tree: &MerkleTree<Keccak256>) {
+        let document = TreeDocument::from_tree(tree);
+        let from_json = tree_from_json(&tree_to_json(tree), Keccak256).unwrap();
+        let from_bytes = tree_from_bytes(&tree_to_bytes(tree), Keccak256).unwrap();
+        for loaded in [from_json, from_bytes] {
+            assert_eq!(loaded.root_hash(), tree.root_hash());
+            assert_eq!(loaded.words, tree.words);
+            assert_eq!(TreeDocument::from_tree(&loaded), document);
+        }
+    }
+
+    #[test]
+    fn trees_round_trip() {
+        let tree = tree();
+        assert_eq!(tree.words[2], "");
+        assert_round_trip(&tree);
+        assert_round_trip(&sorted_tree());
+
+        let hashes: Vec<Digest> = ["a", "b", "c"].iter().map(|word| hasher::keccak_256(word.as_bytes())).collect();
+        let prehashed = tree::build_tree_from_hashes(&hashes, Keccak256, TreeOptions::default()).unwrap();
+        let document = TreeDocument::from_tree(&prehashed);
+        assert!(document.leaves.iter().all(|leaf| leaf.prehashed));
+        assert!(TreeDocument::from_tree(&tree).leaves.iter().all(|leaf| !leaf.prehashed));
+        assert_round_trip(&prehashed);
+    }
+
+    #[test]
+    fn proofs_round_trip() {
+        let mut tree = tree();
+        let root = tree.root_hash().unwrap();
+        let proof = tree.inclusion_proof(3).unwrap();
+        let leaf = tree.nodes[&1][3].hash;
+
+        let json = proof_to_json(&proof);
+        let bytes = proof_to_bytes(&proof);
+        for loaded in [proof_from_json(&json).unwrap(), proof_from_bytes(&bytes).unwrap()] {
+            assert_eq!(proof_to_json(&loaded), json);
+            assert_eq!(proof_to_bytes(&loaded), bytes);
+            assert_eq!(proof::verify(&root, &leaf, 3, &loaded, &Keccak256), Ok(true));
+        }
+    }
+
+    #[test]
+    fn multi_proofs_round_trip() {
+        let mut tree = tree();
+        let root = tree.root_hash().unwrap();
+        let proof = tree.multi_proof(&[0, 3, 4]).unwrap();
+        let leaves: Vec<Digest> = [0, 3, 4].iter().map(|index| tree.nodes[&1][*index].hash).collect();
+
+        let json = multi_proof_to_json(&proof);
+        let bytes = multi_proof_to_bytes(&proof);
+        for loaded in [multi_proof_from_json(&json).unwrap(), multi_proof_from_bytes(&bytes).unwrap()] {
+            assert_eq!(multi_proof_to_json(&loaded), json);
+            assert_eq!(multi_proof_to_bytes(&loaded), bytes);
+            assert_eq!(proof::verify_multi(&root, &leaves, &loaded, &Keccak256), Ok(true));
+        }
+    }
+
+    #[test]
+    fn out_of_order_sorted_trees_are_rejected() {
+        let mut document = TreeDocument::from_tree(&sorted_tree());
+        document.leaves.swap(1, 2);
+        assert_eq!(document.clone().into_tree(Keccak256).unwrap_err(), CodecError::Unsorted { index: 2 });
+        assert_eq!(TreeDocument::from_bytes(&document.to_bytes()).unwrap().into_tree(Keccak256).unwrap_err(), CodecError::Unsorted { index: 2 });
+    }
+
+    #[test]
+    fn a_different_root_is_rejected() {
+        let mut document = TreeDocument::from_tree(&tree());
+        let found = document.root;
+        document.root = Digest([7; 32]);
+        assert_eq!(document.into_tree(Keccak256).unwrap_err(), CodecError::RootMismatch { expected: Digest([7; 32]), found });
+    }
+
+    #[test]
+    fn another_hasher_is_rejected() {
+        let error = tree_from_json(&tree_to_json(&tree()), Sha256).unwrap_err();
+        assert_eq!(error, CodecError::HasherMismatch { expected: Sha256.name().to_string(), found: Keccak256.name().to_string() });
+        assert!(matches!(tree_from_bytes(&tree_to_bytes(&tree()), Sha256), Err(CodecError::HasherMismatch { .. })));
+    }
+
+    #[test]
+    fn leaves_must_match_their_values() {
+        let mut document = TreeDocument::from_tree(&tree());
+        document.leaves[1].value = b"x".to_vec();
+        assert_eq!(document.into_tree(Keccak256).unwrap_err(), CodecError::LeafMismatch { index: 1 });
+
+        // a leaf only passes as prehashed when its value is its hash
+        let mut document = TreeDocument::from_tree(&tree());
+        document.leaves[0].prehashed = true;
+        assert_eq!(document.into_tree(Keccak256).unwrap_err(), CodecError::LeafMismatch { index: 0 });
+    }
+
+    #[test]
+    fn malformed_binary_documents_are_rejected() {
+        let bytes = tree_to_bytes(&tree());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(TreeDocument::from_bytes(&trailing), Err(CodecError::TrailingBytes));
+
+        let mut magic = bytes.clone();
+        magic[0] ^= 1;
+        assert_eq!(TreeDocument::from_bytes(&magic), Err(CodecError::NotADocument));
+
+        let mut version = bytes.clone();
+        version[MAGIC.len()] = VERSION + 1;
+        assert_eq!(TreeDocument::from_bytes(&version), Err(CodecError::UnsupportedVersion(VERSION + 1)));
+
+        assert_eq!(TreeDocument::from_bytes(&bytes[..bytes.len() - 1]), Err(CodecError::UnexpectedEnd));
+
+        let proof = proof_to_bytes(&tree().inclusion_proof(0).unwrap());
+        assert_eq!(TreeDocument::from_bytes(&proof), Err(CodecError::WrongKind { expected: TREE, found: PROOF }));
+        assert!(matches!(multi_proof_from_bytes(&proof), Err(CodecError::WrongKind { expected: MULTI_PROOF, found: PROOF })));
+        assert!(matches!(proof_from_bytes(&bytes), Err(CodecError::WrongKind { expected: PROOF, found: TREE })));
+    }
+
+    #[test]
+    fn malformed_json_documents_are_rejected() {
+        let json = tree_to_json(&tree()).replacen("\"version\": 1", "\"version\": 2", 1);
+        assert_eq!(TreeDocument::from_json(&json), Err(CodecError::UnsupportedVersion(2)));
+
+        let proof = proof_to_json(&tree().inclusion_proof(0).unwrap());
+        assert!(matches!(TreeDocument::from_json(&proof), Err(CodecError::Json(_))));
+        assert!(matches!(multi_proof_from_json(&proof), Err(CodecError::Json(_))));
+    }
+
+    #[test]
+    fn proofs_must_have_the_steps_of_their_index() {
+        let mut proof = tree().inclusion_proof(1).unwrap();
+        proof.steps.pop();
+        assert_eq!(proof_from_bytes(&proof_to_bytes(&proof)).unwrap_err(), CodecError::Proof(ProofError::MalformedProof));
+        assert_eq!(proof_from_json(&proof_to_json(&proof)).unwrap_err(), CodecError::Proof(ProofError::MalformedProof));
+    }
+}
@@ -0,0 +1,340 @@
+//! Trees over the files of a directory, to commit to a released snapshot with one root.
+//!
+//! Each file becomes the word `<relative path> <hash of its contents>`, with `/` between path
+//! components. The words are sorted by path and hashed like any other word, so the manifest from
+//! `Snapshot::manifest` can be fed to `build`, `prove` and `verify` as it is.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::MerkleError;
+use crate::hasher::{self, Digest, MerkleHasher};
+use crate::proof::{self, InclusionProof, ProofError};
+use crate::tree::{self, MerkleTree, TreeOptions};
+
+#[derive(Debug)]
+pub enum DirectoryError {
+    Io(io::Error),
+    /// Paths must be UTF-8 without line breaks, to fit on one line of the manifest
+    UnsupportedPath(PathBuf),
+    /// The leaves stay sorted by path, so no other leaf order can be used
+    SortedOrder,
+    /// Two files with the same relative path
+    DuplicatePath(String),
+    /// A manifest line that isn't a path followed by a hash
+    InvalidEntry(String),
+    /// The snapshot has no file at this path
+    NotFound(String),
+    /// Snapshots built with other hashers or tree options hash the same files differently
+    DifferentSettings,
+    Tree(MerkleError),
+}
+
+impl fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DirectoryError::Io(error) => write!(f, "{}", error),
+            DirectoryError::UnsupportedPath(path) => write!(f, "unsupported path {:?}", path),
+            DirectoryError::SortedOrder => write!(f, "files must stay sorted by path"),
+            DirectoryError::DuplicatePath(path) => write!(f, "{} is listed twice", path),
+            DirectoryError::InvalidEntry(line) => write!(f, "bad manifest line {}", line),
+            DirectoryError::NotFound(path) => write!(f, "{} is not in the snapshot", path),
+            DirectoryError::DifferentSettings => write!(f, "the snapshots were built with different hashers or tree options"),
+            DirectoryError::Tree(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DirectoryError {}
+
+impl From<io::Error> for DirectoryError {
+    fn from(error: io::Error) -> Self {
+        DirectoryError::Io(error)
+    }
+}
+
+impl From<MerkleError> for DirectoryError {
+    fn from(error: MerkleError) -> Self {
+        DirectoryError::Tree(error)
+    }
+}
+
+/// One file of a snapshot: its path relative to the directory, and the hash of its contents
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileEntry {
+    pub path: String,
+    pub hash: Digest,
+}
+
+impl FileEntry {
+    /// The word this file is hashed as, also its line in the manifest
+    pub fn word(&self) -> String {
+        entry_word(&self.path, &self.hash)
+    }
+
+    /// Read back a word from `word`. The hash is the last field, so paths may hold spaces.
+    pub fn parse(word: &str) -> Option<FileEntry> {
+        let (path, hash) = word.rsplit_once(' ')?;
+        if path.is_empty() {
+            return None;
+        }
+        Some(FileEntry { path: path.to_string(), hash: Digest::from_hex(hash)? })
+    }
+}
+
+/// The word for the file at `path` whose contents hash to `hash`
+pub fn entry_word(path: &str, hash: &Digest) -> String {
+    format!("{} {}", path, hash.to_hex())
+}
+
+/// What happened to a file between two snapshots
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Modified(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(path) => write!(f, "A {}", path),
+            Change::Removed(path) => write!(f, "D {}", path),
+            Change::Modified(path) => write!(f, "M {}", path),
+        }
+    }
+}
+
+/// The files of a directory sorted by path, and the tree over them
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Snapshot<H: MerkleHasher> {
+    pub entries: Vec<FileEntry>,
+    pub tree: MerkleTree<H>,
+}
+
+impl<H: MerkleHasher> Snapshot<H> {
+    pub fn root(&self) -> Option<Digest> {
+        self.tree.root_hash()
+    }
+
+    /// Leaf index of the file at `path`
+    pub fn index_of(&self, path: &str) -> Option<usize> {
+        self.entries.binary_search_by(|entry| entry.path.as_str().cmp(path)).ok()
+    }
+
+    /// Proof that the file at `path`, with its contents at snapshot time, belongs to the snapshot
+    pub fn proof(&mut self, path: &str) -> Result<InclusionProof, DirectoryError> {
+        let index = self.index_of(path).ok_or_else(|| DirectoryError::NotFound(path.to_string()))?;
+        Ok(self.tree.inclusion_proof(index)?)
+    }
+
+    /// One word per line, in leaf order
+    pub fn manifest(&self) -> String {
+        self.entries.iter().map(|entry| entry.word() + "\n").collect()
+    }
+
+    /// Files added, removed or modified going from `self` to `newer`, which must be built with the
+    /// same hasher and options. Snapshots with the same root are equal without comparing their files.
+    pub fn diff<N: MerkleHasher>(&self, newer: &Snapshot<N>) -> Result<Vec<Change>, DirectoryError> {
+        if self.tree.hasher.name() != newer.tree.hasher.name() || self.tree.options != newer.tree.options {
+            return Err(DirectoryError::DifferentSettings);
+        }
+        if self.root() == newer.root() {
+            return Ok(Vec::new());
+        }
+        Ok(diff(&self.entries, &newer.entries))
+    }
+}
+
+/// The files under `dir` with the hashes of their contents, sorted by path. Files are streamed
+/// through the hasher rather than read whole. Symbolic links are left out, so the snapshot only
+/// covers what is stored in the directory.
+pub fn list_files<H: MerkleHasher>(dir: &Path, hasher: &H) -> Result<Vec<FileEntry>, DirectoryError> {
+    let mut entries = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for item in fs::read_dir(&current)? {
+            let item = item?;
+            let file_type = item.file_type()?;
+            if file_type.is_dir() {
+                pending.push(item.path());
+            } else if file_type.is_file() {
+                let path = item.path();
+                let relative = relative_path(dir, &path).ok_or_else(|| DirectoryError::UnsupportedPath(path.clone()))?;
+                let hash = hasher.hash_reader(&mut io::BufReader::new(fs::File::open(&path)?))?;
+                entries.push(FileEntry { path: relative, hash });
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// `path` relative to `dir`, joined with `/`
+fn relative_path(dir: &Path, path: &Path) -> Option<String> {
+    let components = path.strip_prefix(dir).ok()?.components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<&str>>>()?;
+    let relative = components.join("/");
+    (!relative.contains(['\n', '\r'])).then_some(relative)
+}
+
+/// Hash every file under `dir` and build the snapshot tree over them
+pub fn snapshot<H: MerkleHasher>(dir: &Path, hasher: H, options: TreeOptions) -> Result<Snapshot<H>, DirectoryError> {
+    let entries = list_files(dir, &hasher)?;
+    from_entries(entries, hasher, options)
+}
+
+/// Build the snapshot tree over `entries`, sorting them by path first
+pub fn from_entries<H: MerkleHasher>(mut entries: Vec<FileEntry>, hasher: H, options: TreeOptions) -> Result<Snapshot<H>, DirectoryError> {
+    if options.leaf_order.is_sorted() {
+        return Err(DirectoryError::SortedOrder);
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].path == pair[1].path) {
+        return Err(DirectoryError::DuplicatePath(pair[0].path.clone()));
+    }
+
+    let words: Vec<String> = entries.iter().map(FileEntry::word).collect();
+    let tree = tree::build_tree_with(words, hasher, options)?;
+    Ok(Snapshot { entries, tree })
+}
+
+/// Build a snapshot from the lines of a manifest written by `Snapshot::manifest`
+pub fn from_manifest<H: MerkleHasher>(manifest: &str, hasher: H, options: TreeOptions) -> Result<Snapshot<H>, DirectoryError> {
+    let entries = manifest.lines().filter(|line| !line.is_empty())
+        .map(|line| FileEntry::parse(line).ok_or_else(|| DirectoryError::InvalidEntry(line.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+    from_entries(entries, hasher, options)
+}
+
+/// Check that a file at `path` with `contents` belongs to the snapshot with `root`, using a proof
+/// from `Snapshot::proof`. Returns `Ok(false)` when the file is not in the snapshot as it is.
+pub fn verify_file<H: MerkleHasher>(root: &Digest, path: &str, contents: &[u8], proof: &InclusionProof, hasher: &H) -> Result<bool, ProofError> {
+    let word = entry_word(path, &hasher.hash(contents));
+    let leaf = hasher::hash_word(hasher, &word, &proof.options);
+    proof::verify(root, &leaf, proof.leaf_index, proof, hasher)
+}
+
+/// Files added, removed or modified going from `old` to `new`, both sorted by path
+pub fn diff(old: &[FileEntry], new: &[FileEntry]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let (mut old, mut new) = (old.iter().peekable(), new.iter().peekable());
+    loop {
+        let order = match (old.peek(), new.peek()) {
+            (Some(before), Some(after)) => before.path.cmp(&after.path),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match order {
+            Ordering::Less => changes.extend(old.next().map(|entry| Change::Removed(entry.path.clone()))),
+            Ordering::Greater => changes.extend(new.next().map(|entry| Change::Added(entry.path.clone()))),
+            Ordering::Equal => {
+                if let (Some(before), Some(after)) = (old.next(), new.next()) {
+                    if before.hash != after.hash {
+                        changes.push(Change::Modified(after.path.clone()));
+                    }
+                }
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::{HashAlgorithm, Keccak256, Sha256};
+    use crate::tree::OddNodeRule;
+
+    const FILES: [(&str, &str); 4] = [
+        ("Cargo.toml", "[package]"),
+        ("docs/release notes.md", "# 1.0"),
+        ("src/lib.rs", "pub mod tree;"),
+        ("src/main.rs", "fn main() {}"),
+    ];
+
+    fn entries(files: &[(&str, &str)]) -> Vec<FileEntry> {
+        files.iter().map(|(path, contents)| FileEntry { path: path.to_string(), hash: Keccak256.hash(contents.as_bytes()) }).collect()
+    }
+
+    fn snapshot_of(files: &[(&str, &str)]) -> Snapshot<Keccak256> {
+        from_entries(entries(files), Keccak256, TreeOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn files_with_spaces_in_their_path_verify() {
+        let snapshot = snapshot_of(&FILES);
+        let mut loaded = from_manifest(&snapshot.manifest(), Keccak256, TreeOptions::default()).unwrap();
+        assert_eq!(loaded.entries, snapshot.entries);
+        assert_eq!(loaded.root(), snapshot.root());
+
+        let root = loaded.root().unwrap();
+        let proof = loaded.proof("docs/release notes.md").unwrap();
+        assert_eq!(verify_file(&root, "docs/release notes.md", b"# 1.0", &proof, &Keccak256), Ok(true));
+        assert_eq!(verify_file(&root, "docs/release notes.md", b"# 1.1", &proof, &Keccak256), Ok(false));
+        assert_eq!(verify_file(&root, "docs/release.md", b"# 1.0", &proof, &Keccak256), Ok(false));
+        assert!(matches!(loaded.proof("docs/release"), Err(DirectoryError::NotFound(_))));
+    }
+
+    #[test]
+    fn bad_manifests_are_rejected() {
+        assert!(matches!(from_manifest("src/lib.rs\n", Keccak256, TreeOptions::default()), Err(DirectoryError::InvalidEntry(_))));
+        let line = FileEntry { path: "a".to_string(), hash: Digest::ZERO }.word();
+        let twice = format!("{}\n{}\n", line, line);
+        assert!(matches!(from_manifest(&twice, Keccak256, TreeOptions::default()), Err(DirectoryError::DuplicatePath(_))));
+    }
+
+    #[test]
+    fn diff_lists_added_removed_and_modified_files() {
+        let old = snapshot_of(&FILES);
+        let new = snapshot_of(&[
+            ("Cargo.toml", "[package]"),
+            ("README.md", "# merkle"),
+            ("src/lib.rs", "pub mod tree;\npub mod proof;"),
+            ("src/main.rs", "fn main() {}"),
+        ]);
+        assert_eq!(old.diff(&new).unwrap(), [
+            Change::Added("README.md".to_string()),
+            Change::Removed("docs/release notes.md".to_string()),
+            Change::Modified("src/lib.rs".to_string()),
+        ]);
+        assert_eq!(old.diff(&old.clone()).unwrap(), []);
+    }
+
+    #[test]
+    fn diff_needs_the_same_settings() {
+        let old = snapshot_of(&FILES);
+        let sha256 = from_entries(entries(&FILES), Sha256, TreeOptions::default()).unwrap();
+        assert!(matches!(old.diff(&sha256), Err(DirectoryError::DifferentSettings)));
+
+        let options = TreeOptions { odd_rule: OddNodeRule::Promote, ..Default::default() };
+        let promoted = from_entries(entries(&FILES), Keccak256, options).unwrap();
+        assert!(matches!(old.diff(&promoted), Err(DirectoryError::DifferentSettings)));
+    }
+
+    #[test]
+    fn listed_files_are_hashed_as_read_whole() {
+        let dir = std::env::temp_dir().join(format!("merkle-directory-{}", std::process::id()));
+        let large: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        fs::create_dir_all(dir.join("nested dir")).unwrap();
+        fs::write(dir.join("small.txt"), b"small").unwrap();
+        fs::write(dir.join("nested dir").join("large.bin"), &large).unwrap();
+
+        for algorithm in HashAlgorithm::ALL {
+            let listed = list_files(&dir, &algorithm).unwrap();
+            assert_eq!(listed, [
+                FileEntry { path: "nested dir/large.bin".to_string(), hash: algorithm.hash(&large) },
+                FileEntry { path: "small.txt".to_string(), hash: algorithm.hash(b"small") },
+            ], "{}", algorithm.name());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+
+use crate::hasher::Digest;
+use crate::proof::ProofError;
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum MerkleError {
+    /// The tree has no leaves
+    EmptyTree,
+    /// No leaf at `index` in a tree of `size` leaves
+    IndexOutOfRange { index: usize, size: usize },
+    /// A level the tree should have is missing from `nodes`
+    MissingLevel(u32),
+    /// The proof doesn't fit the leaf or tree it is checked against
+    MalformedProof,
+    /// Recomputing the root from the proof gave `found` instead of `expected`
+    HashMismatch { expected: Digest, found: Digest },
+    /// The proof was made for the tree with root `proof_root`, which has since changed to `root`
+    StaleProof { proof_root: Digest, root: Digest },
+    /// The word asked to be excluded is the leaf at `index`
+    WordIncluded { index: usize },
+    /// Not 64 hex characters
+    InvalidHex(String),
+    /// Exclusion proofs need leaves built with a sorted `LeafOrder`, and still in that order
+    Unsorted,
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerkleError::EmptyTree => write!(f, "the tree has no leaves"),
+            MerkleError::IndexOutOfRange { index, size } => write!(f, "index {} is out of range for {} leaves", index, size),
+            MerkleError::MissingLevel(level) => write!(f, "level {} of the tree is missing", level),
+            MerkleError::MalformedProof => write!(f, "malformed proof"),
+            MerkleError::HashMismatch { expected, found } => write!(f, "proof leads to root {}, expected {}", found, expected),
+            MerkleError::StaleProof { proof_root, root } => write!(f, "proof was made for root {}, the tree is now at {}", proof_root, root),
+            MerkleError::WordIncluded { index } => write!(f, "the word is in the tree at index {}", index),
+            MerkleError::Unsorted => write!(f, "the leaves are not sorted"),
+            MerkleError::InvalidHex(hex) => write!(f, "{} is not a 32-byte hex hash", hex),
+        }
+    }
+}
+
+impl std::error::Error for MerkleError {}
+
+impl From<ProofError> for MerkleError {
+    fn from(error: ProofError) -> Self {
+        match error {
+            ProofError::IndexOutOfRange { index, tree_size } => MerkleError::IndexOutOfRange { index, size: tree_size },
+            // a proof made for another leaf doesn't describe this one
+            ProofError::IndexMismatch { .. } | ProofError::MalformedProof => MerkleError::MalformedProof,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use bevy::{asset::Handle, color::Color, math::{Quat, Vec3}, prelude::{Commands, Component, Transform}, sprite::{Sprite, SpriteBundle}, text::{Font, Text, Text2dBundle, TextStyle}};
+
+use merkle::{hasher::MerkleHasher, tree::{MerkleTree, Node}};
+
+/// Size of a node sprite on screen
+pub const NODE_SIZE: (f32, f32) = (150.0, 50.0);
+
+/// Horizontal room the leaves are spread across
+const GRAPH_WIDTH: f32 = 1200.0;
+
+/// Gap between neighbouring leaves
+const NODE_GAP: f32 = 10.0;
+
+/// Nodes aren't shrunk below this, wider trees run past the screen and are panned along instead
+const MIN_NODE_WIDTH: f32 = 20.0;
+
+/// On-screen counterpart of a `tree::Node`, carrying the layout positions.
+/// `hash` is the text shown in the node: the hex digest, or the word itself for word nodes.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Component)]
+pub struct GraphNode {
+    pub hash: String,
+    pub level: u32,
+    pub parent_index: (u32, u32),
+    pub index: usize,
+    pub position: (f32, f32),
+    pub is_hash: bool
+}
+
+#[derive(Component)]
+pub struct BranchMarker;
+
+#[derive(Component)]
+pub struct NodeTextMarker{
+    pub node_index: usize,
+    pub node_level: u32
+}
+
+impl From<&Node> for GraphNode {
+    fn from(node: &Node) -> Self {
+        GraphNode{
+            hash: node.hash.to_hex(),
+            level: node.level,
+            parent_index: node.parent_index,
+            index: node.index,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for GraphNode {
+    fn default() -> Self {
+        GraphNode{
+            hash: "".to_string(),
+            level: 0,
+            parent_index: (0, 0),
+            index: 0,
+            position: (0.0, 0.0),
+            is_hash: true
+        }
+    }
+}
+
+/// Width of a node when `leaf_count` leaves are drawn side by side
+fn node_width(leaf_count: usize) -> f32 {
+    // shrink the nodes when the words don't fit across the screen, down to a readable minimum
+    NODE_SIZE.0.min(GRAPH_WIDTH / leaf_count.max(1) as f32 - NODE_GAP).max(MIN_NODE_WIDTH)
+}
+
+/// Width the leaves of a tree with `leaf_count` leaves take up, wider than the screen past a few hundred leaves
+pub fn layout_width(leaf_count: usize) -> f32 {
+    leaf_count as f32 * (node_width(leaf_count) + NODE_GAP)
+}
+
+/// Lay out the tree from the root up to the words and spawn its sprites.
+/// Leaves are spread evenly across the screen and each parent sits above the middle of its children,
+/// so any number of words can be drawn.
+pub fn graph<H: MerkleHasher>(tree: &MerkleTree<H>, screen_height: f32, mut commands: Commands,handle: &Handle<Font>) {
+    let leaf_count = tree.nodes.get(&1).map_or(0, |nodes| nodes.len());
+    if leaf_count == 0 {
+        return;
+    }
+
+    let size = (node_width(leaf_count), NODE_SIZE.1);
+
+    let root_y = -(screen_height / 2.0) + 50.0; // Origin is at the center, root sits at the bottom
+    let word_gap = 30.0;
+    let available_height = screen_height - 100.0 - size.1 - word_gap;
+    let level_gap = if tree.levels > 1 {
+        (available_height / (tree.levels - 1) as f32).min(120.0)
+    } else {
+        0.0
+    };
+
+    let mut graph_nodes: HashMap<u32, Vec<GraphNode>> = tree.nodes.iter()
+        .map(|(level, nodes)| (*level, nodes.iter().map(GraphNode::from).collect()))
+        .collect();
+
+    // words first, then each level above is centred over the one below
+    for current_level in 1..=tree.levels {
+        let y = root_y + (tree.levels - current_level) as f32 * level_gap;
+
+        let child_positions: Vec<(f32, f32)> = if current_level == 1 {
+            Vec::new()
+        } else {
+            graph_nodes.get(&(current_level - 1)).map_or(Vec::new(), |nodes| nodes.iter().map(|node| node.position).collect())
+        };
+
+        if let Some(nodes) = graph_nodes.get_mut(&current_level) {
+            for node in nodes.iter_mut() {
+                let x = if current_level == 1 {
+                    (node.index as f32 - (leaf_count as f32 - 1.0) / 2.0) * (size.0 + NODE_GAP)
+                } else {
+                    let left = child_positions[node.parent_index.0 as usize];
+                    let right = child_positions[node.parent_index.1 as usize];
+                    (left.0 + right.0) / 2.0
+                };
+                node.position = (x, y);
+
+                draw_node(&mut commands, node, size, handle,true);
+
+                if current_level == 1 {
+                    continue;
+                }
+
+                // Draw the lines to the child nodes, from the top of this node to the bottom of each child
+                let start_point = (x, y + size.1 / 2.0);
+                let (left_index, right_index) = node.parent_index;
+                for child_index in [left_index, right_index] {
+                    let child = child_positions[child_index as usize];
+                    draw_line(&mut commands, start_point, (child.0, child.1 - size.1 / 2.0), 1.0);
+                    if left_index == right_index {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // draw words
+    if let Some(prime_nodes) = graph_nodes.get(&1) {
+        for (i, node) in prime_nodes.iter().enumerate() {
+
+            // Draw a straight short line from node to + 30 on y axis
+            let start_point = (node.position.0, node.position.1 + size.1 / 2.0);
+            let size_y = size.1;
+            let end_point = (start_point.0, start_point.1 + word_gap);
+            draw_line(&mut commands, start_point, end_point, 1.0);
+            let word_node = GraphNode{
+                hash: tree.words[i].clone(),
+                level: 0,
+                parent_index: (0, 0),
+                index: i,
+                position: (start_point.0, end_point.1 + size_y / 2.0),
+                is_hash: false
+            };
+            // Then draw a node with the word in it
+            draw_node(&mut commands, &word_node, size, handle,false);
+        }
+    }
+}
+
+
+fn draw_line(commands: &mut Commands, point1: (f32, f32), point5: (f32, f32), thickness: f32) {
+    
+    let mid_point = (
+        (point1.0 + point5.0) / 2.0,
+        (point1.1 + point5.1) / 2.0,
+    );
+
+    // Calculate the angle of rotation
+    let angle = (point5.1 - point1.1).atan2(point5.0 - point1.0);
+
+    // Calculate the distance between the two points
+    let length = ((point5.0 - point1.0).powi(2) + (point5.1 - point1.1).powi(2)).sqrt();
+
+    // Spawn the line (as a scaled and rotated sprite)
+    commands.spawn(SpriteBundle {
+        transform: Transform {
+            translation: Vec3::new(mid_point.0, mid_point.1, 0.0),
+            rotation: Quat::from_rotation_z(angle),           
+            scale: Vec3::new(length, thickness, 1.0),
+        },
+        sprite: Sprite {
+            color: Color::BLACK,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .insert(BranchMarker);
+
+    println!("Drawing Line from {:?} to {:?}", point1, point5);
+}
+
+fn draw_node(commands: &mut Commands, node: &GraphNode, size: (f32, f32),handle: &Handle<Font>, is_hash: bool) {
+    let (x, y) = node.position;
+    let (size_x,size_y) = size;
+
+    let display_text = if is_hash {
+        MerkleTree::format_hash(&node.hash, 6, 6, "...")
+    } else {
+        node.hash.clone()
+    };
+
+    commands.spawn(SpriteBundle {
+        sprite: Sprite {
+            color: Color::BLACK,
+            ..Default::default()
+        },
+        //color: Color::srgb(0.5, 0.5, 1.0)
+        transform: Transform {
+            translation: Vec3::new(x, y, 0.0),
+            scale: Vec3::new(size_x,size_y, 1.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .insert(node.clone());
+
+     // Adjust text size based on content length
+    let calculated_font_size = (size_y * 0.5).min(size_x / (display_text.len() as f32 * 0.5));
+
+    // Draw the text
+    commands.spawn(Text2dBundle {
+        text: Text::from_section(display_text, 
+        TextStyle { 
+            font: handle.clone(),
+             font_size: calculated_font_size, color: Color::WHITE }),
+        transform: Transform::from_translation(Vec3::new(x, y, 10.0)),
+        ..Default::default()
+    })
+    .insert(NodeTextMarker{node_index: node.index, node_level: node.level}); 
+}
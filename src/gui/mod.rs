@@ -0,0 +1,1335 @@
+use bevy::{prelude::*, window::{PrimaryWindow, WindowPlugin}};
+use merkle::{chunked, error::MerkleError, hasher::{self, HashAlgorithm, MerkleHasher}, tree::{self, LeafOrder, MerkleTree, TreeOptions}};
+//use keccak_hash;
+
+mod graph;
+mod session;
+
+//.insert_resource(ClearColor(Color::srgb(0.1216, 0.2039, 0.3451)))
+
+/// Open the visualiser window
+pub fn run() {
+    App::new()
+    .init_resource::<GameState>()
+    .insert_resource(ClearColor(Color::WHITE))
+    .add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            title: "Merkle".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+        .add_systems(Startup, start_up)
+        .add_systems(Update, (check_keyboards, drop_file, sprite_update, text_bar_update, update_loop_text, update_loop_tree.after(button_system), button_system, pan_camera, update_proof_outcome, update_consistency_text, apply_changed_path.after(update_loop_tree), animate_path_pulse.after(apply_changed_path)))
+        .run();
+}
+
+#[derive(Resource)]
+struct GameState{
+    pub current_text: String,
+    pub display_text: String,
+    pub previous_text: String,
+    pub handle: Handle<Font>,
+    pub toggle_input: bool,
+    pub mouse_position: (f32, f32),
+    pub tree: MerkleTree<HashAlgorithm>,
+    pub hasher: HashAlgorithm,
+    /// Leaf order for the next build, sorted orders allow exclusion proofs
+    pub leaf_order: LeafOrder,
+    pub select_node: bool,
+    pub selected_node: Option<graph::GraphNode>,
+    /// Word indices shift-clicked in proof mode, proven together with one multiproof
+    pub proof_selection: Vec<usize>,
+    /// Whether the last build extends the tree it replaced, checked with a consistency proof
+    pub consistency: Option<String>,
+    /// Nodes changed by the last push or update, as (level, index) with words on level 0, waiting to be redrawn
+    pub changed_path: Vec<(u32, usize)>,
+    pub mode: MerkleMode,
+    pub hovered_button: Option<Entity>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState{
+            current_text: "Binary Merkle Tree Demo".to_string(),
+            display_text: "Binary Merkle Tree Demo".to_string(),
+            previous_text: "".to_string(),
+            handle: Handle::default(),
+            toggle_input: true,
+            mouse_position: (0.0, 0.0),
+            tree: MerkleTree { 
+                ..Default::default()
+             },
+            hasher: HashAlgorithm::default(),
+            leaf_order: LeafOrder::default(),
+            select_node: false,
+            selected_node: None,
+            proof_selection: Vec::new(),
+            consistency: None,
+            changed_path: Vec::new(),
+            mode: MerkleMode::BuildTree,
+            hovered_button: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum MerkleMode{ 
+    InclusionProof,
+    RebuildTree,
+    BuildTree
+}
+
+const MERKLE_MODE_STRINGS: [&str; 3] = ["Proof", "Rebuild", "Build"];
+const BUTTON_HOVER_COLOR: Color = Color::BLACK;
+/// How long a node changed by a push or update stays lit
+const PATH_PULSE_SECONDS: f32 = 1.5;
+
+#[derive(Component)]
+struct TextBarMarker;
+
+#[derive(Component)]
+struct TextBarTextMarker;
+
+/// Stands in for a tombstoned word in the text bar, so rebuilding keeps its slot.
+/// Only letters can be typed, so no real word looks like it.
+const TOMBSTONE_WORD: &str = "_";
+
+/// How fast the arrow keys pan along a tree wider than the window, in pixels per second
+const PAN_SPEED: f32 = 600.0;
+
+/// Most leaves drawn for a dropped file, lower levels are left out
+const DROPPED_FILE_LEAVES: usize = 16;
+
+/// Label next to the proven word, saying why its proof passed or failed
+#[derive(Component)]
+struct ProofOutcomeMarker;
+
+/// Label under the mode buttons saying whether the current tree extends the previous one
+#[derive(Component)]
+struct ConsistencyTextMarker;
+
+/// A node whose hash just changed, fading from orange back to black
+#[derive(Component)]
+struct PathPulse {
+    remaining: f32,
+}
+
+/// Button cycling through the hash algorithms used for the next build
+#[derive(Component)]
+struct HasherButtonMarker;
+
+/// Button cycling through the leaf orders used for the next build
+#[derive(Component)]
+struct LeafOrderButtonMarker;
+
+
+
+fn start_up(mut commands: Commands, asset_server: Res<AssetServer>, mut state: ResMut<GameState>){
+    let font_handle = asset_server.load("fonts/JetBrainsMono-Regular.ttf");
+    let input_texture = asset_server.load("images/input_box.png");
+    state.handle = font_handle.clone();
+
+    commands.spawn(Camera2dBundle{
+        transform: Transform::from_xyz(0.0, 0.0, 1000.0),
+        ..Default::default()
+    });
+
+
+    // Spawn opaque layer
+    commands.spawn(SpriteBundle {
+        sprite: Sprite {
+            color: Color::NONE, // Initially hidden
+            custom_size: Some(Vec2::new(800.0, 600.0)),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .insert(TextBarMarker)
+    .with_children(|parent| {
+        parent.spawn(SpriteBundle {
+            texture: input_texture.clone(),
+            sprite: Sprite {
+                color: Color::NONE,
+                custom_size: Some(Vec2::new(700.0, 50.0)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0.0, 200.0, 3.0),
+            ..Default::default()
+        });
+    });
+
+    // Spawn input/display text
+    commands.spawn(Text2dBundle {
+        text: Text::from_section(state.display_text.clone(), TextStyle { font: font_handle.clone(), font_size: 18.0, color: Color::WHITE }),
+        transform: Transform::from_translation(Vec3::new(0.0, 200.0, 4.0)),
+        ..Default::default()
+    })
+    .insert(TextBarTextMarker);
+
+    // Spawn the proof outcome label, moved next to the proven word by update_proof_outcome
+    commands.spawn(Text2dBundle {
+        text: Text::from_section("", TextStyle { font: font_handle.clone(), font_size: 16.0, color: Color::BLACK }),
+        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
+        ..Default::default()
+    })
+    .insert(ProofOutcomeMarker);
+
+    // Spawn the consistency label, filled in by update_consistency_text after each build
+    commands.spawn(TextBundle {
+        text: Text::from_section("", TextStyle { font: font_handle.clone(), font_size: 16.0, color: Color::BLACK }),
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(100.0),
+            right: Val::Px(100.0),
+            ..default()
+        },
+        ..default()
+    })
+    .insert(ConsistencyTextMarker);
+
+
+
+    // Spawn mode buttons
+    commands
+    .spawn(NodeBundle {
+        style: Style {
+            width: Val::Auto,
+            height: Val::Auto,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            position_type: PositionType::Absolute,
+            top: Val::Px(50.0), 
+            right: Val::Px(90.0),
+            ..default()
+        },
+        ..default()
+    })
+    .with_children(|parent| {
+        // Proof Button
+        parent.spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(100.0),
+                height: Val::Px(40.0),
+                margin: UiRect {
+                    left: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(2.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            border_radius: BorderRadius{ top_left: Val::Px(5.0), top_right: Val::Px(5.0), bottom_left:Val::Px(5.0), bottom_right: Val::Px(5.0)},
+            background_color: Color::BLACK.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Proof",
+                TextStyle {
+                    font: asset_server.load("fonts/JetBrainsMono-Regular.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+
+        // Rebuild Button
+        parent.spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(100.0),
+                height: Val::Px(40.0),
+                margin: UiRect {
+                    left: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(2.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            border_radius: BorderRadius{ top_left: Val::Px(5.0), top_right: Val::Px(5.0), bottom_left:Val::Px(5.0), bottom_right: Val::Px(5.0)},
+            background_color: Color::BLACK.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Rebuild",
+                TextStyle {
+                    font: asset_server.load("fonts/JetBrainsMono-Regular.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+
+        // Build Button
+        parent.spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(100.0),
+                height: Val::Px(40.0),
+                margin: UiRect {
+                    left: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(2.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            border_radius: BorderRadius{ top_left: Val::Px(5.0), top_right: Val::Px(5.0), bottom_left:Val::Px(5.0), bottom_right: Val::Px(5.0)},
+            background_color: Color::BLACK.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Build",
+                TextStyle {
+                    font: asset_server.load("fonts/JetBrainsMono-Regular.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+
+        // Hasher Button
+        parent.spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(160.0),
+                height: Val::Px(40.0),
+                margin: UiRect {
+                    left: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(2.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            border_radius: BorderRadius{ top_left: Val::Px(5.0), top_right: Val::Px(5.0), bottom_left:Val::Px(5.0), bottom_right: Val::Px(5.0)},
+            background_color: Color::BLACK.into(),
+            ..default()
+        })
+        .insert(HasherButtonMarker)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                state.hasher.name(),
+                TextStyle {
+                    font: asset_server.load("fonts/JetBrainsMono-Regular.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+
+        // Leaf Order Button
+        parent.spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(120.0),
+                height: Val::Px(40.0),
+                margin: UiRect {
+                    left: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(2.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            border_radius: BorderRadius{ top_left: Val::Px(5.0), top_right: Val::Px(5.0), bottom_left:Val::Px(5.0), bottom_right: Val::Px(5.0)},
+            background_color: Color::BLACK.into(),
+            ..default()
+        })
+        .insert(LeafOrderButtonMarker)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                state.leaf_order.name(),
+                TextStyle {
+                    font: asset_server.load("fonts/JetBrainsMono-Regular.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+    });
+
+}
+
+
+#[allow(clippy::type_complexity)]
+fn button_system(
+    mut query_set: ParamSet<(
+        Query<(&mut BackgroundColor, &mut BorderColor, &Children, Entity), With<Button>>,  // For all buttons
+        Query<(&Interaction, &mut BackgroundColor, &mut BorderColor, &Children, Entity), (Changed<Interaction>, With<Button>)>,  // For interaction changes
+    )>,
+    mut text_query: Query<&mut Text>,
+    hasher_buttons: Query<(), With<HasherButtonMarker>>,
+    leaf_order_buttons: Query<(), With<LeafOrderButtonMarker>>,
+    mut state: ResMut<GameState>,
+) {
+
+    let mut interaction_happened = false;
+
+    //println!("Interaction triggered!");
+    for (interaction, mut background_color, mut border_color, children, entity) in query_set.p1().iter_mut() {
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            //println!("Interaction text: {:?}",text);
+            match *interaction {
+                Interaction::Pressed => {
+                    // Check which button was pressed based on the text
+                    let proof = MERKLE_MODE_STRINGS[0];
+                    let rebuild = MERKLE_MODE_STRINGS[1];
+                    let build = MERKLE_MODE_STRINGS[2];
+
+                    // Update state based on button text
+                    if hasher_buttons.contains(entity) {
+                        // picks the hasher for the next build, the current tree keeps its own
+                        state.hasher = state.hasher.next();
+                        text.sections[0].value = state.hasher.name().to_string();
+                    } else if leaf_order_buttons.contains(entity) {
+                        state.leaf_order = state.leaf_order.next();
+                        text.sections[0].value = state.leaf_order.name().to_string();
+                    } else if text.sections[0].value.as_str() == proof {
+                        state.mode = MerkleMode::InclusionProof;
+                        state.tree.multi_proof = None;
+                        state.tree.exclusion_proof = None;
+                        state.proof_selection.clear();
+
+                        // an edited word that isn't in a sorted tree gets an exclusion proof
+                        let word_to_prove = state.tree.word_to_prove.as_ref().map(|word_to_prove| word_to_prove.word.clone());
+                        if let Some(word) = word_to_prove.filter(|_| state.tree.options.leaf_order.is_sorted()) {
+                            match state.tree.exclusion_proof(&word) {
+                                Ok(_) | Err(MerkleError::WordIncluded { .. }) => {}
+                                Err(error) => show_error(&mut state, &error),
+                            }
+                        }
+
+                        let selected_node_exists = state.selected_node.is_some() && state.tree.exclusion_proof.is_none();
+                        if selected_node_exists {
+                            let selected_node = state.selected_node.as_ref().map_or(0, |node| node.index);
+                            state.proof_selection.push(selected_node);
+                            if let Err(error) = state.tree.inclusion_proof(selected_node) {
+                                show_error(&mut state, &error);
+                            }
+                        }
+
+                        // call state.tree.inclusion_proof() which will set the proof nodes to be highlighted, i.e the path to the root
+                        // So the level and the index of the nodes to be highlighted
+                    } else if text.sections[0].value.as_str() == rebuild {
+                        state.mode = MerkleMode::RebuildTree;
+                        state.tree.proof = None;
+                        state.tree.multi_proof = None;
+                        state.tree.exclusion_proof = None;
+                    } else if text.sections[0].value.as_str() == build {
+                        state.mode = MerkleMode::BuildTree;
+                    }
+
+
+                    // Change the button border to indicate selection
+                    border_color.0 = BUTTON_HOVER_COLOR;
+                    *background_color = BUTTON_HOVER_COLOR.into();
+                    text.sections[0].style.color = Color::WHITE;
+                    interaction_happened = true;
+
+                }
+                Interaction::Hovered => {
+                    // Highlight button on hover
+                    println!("Hovered changing border colour");
+                    border_color.0 = BUTTON_HOVER_COLOR;
+                    *background_color = BUTTON_HOVER_COLOR.into();
+                    text.sections[0].style.color = Color::WHITE;
+                    interaction_happened = true;
+                    state.hovered_button = Some(entity);
+
+                }
+                Interaction::None => {
+                    if state.hovered_button == Some(entity) {
+                        border_color.0 = Color::BLACK;
+                        *background_color = Color::WHITE.into();
+                        text.sections[0].style.color = Color::BLACK;
+                        state.hovered_button = None;
+                    };
+
+                    println!("Mode: {:?}",state.mode);
+                }
+            }
+        }
+    }
+
+    if !interaction_happened{
+        for (mut background_color, mut border_color, children, entity) in query_set.p0().iter_mut() {
+            if let Ok(mut text) = text_query.get_mut(children[0]) {
+    
+                border_color.0 = Color::BLACK;
+                *background_color = Color::WHITE.into();
+                text.sections[0].style.color = Color::BLACK;
+
+                if let Some(hovered_button) = state.hovered_button {
+                    if hovered_button == entity {
+                        border_color.0 = BUTTON_HOVER_COLOR;
+                        *background_color = BUTTON_HOVER_COLOR.into();
+                        text.sections[0].style.color = Color::WHITE;
+                        continue; 
+                    }
+                }
+
+                if (state.mode == MerkleMode::InclusionProof && text.sections[0].value.as_str() == MERKLE_MODE_STRINGS[0])
+                || (state.mode == MerkleMode::RebuildTree && text.sections[0].value.as_str() == MERKLE_MODE_STRINGS[1])
+                || (state.mode == MerkleMode::BuildTree && text.sections[0].value.as_str() == MERKLE_MODE_STRINGS[2]) {
+                    border_color.0 = BUTTON_HOVER_COLOR;
+                    *background_color = BUTTON_HOVER_COLOR.into();
+                    text.sections[0].style.color = Color::WHITE;
+                }
+            }
+        }
+    }
+
+}
+
+
+
+
+/// Show an error from the tree in the text bar instead of the built text
+fn show_error(state: &mut GameState, error: &dyn std::error::Error) {
+    warn!("{}", error);
+    state.display_text = format!("Error: {}", error);
+    state.toggle_input = false;
+}
+
+/// The words of `tree` as they go in the text bar, with tombstones shown as `TOMBSTONE_WORD`
+fn tree_text(tree: &MerkleTree<HashAlgorithm>) -> String {
+    tree.words.iter()
+        .map(|word| if word.is_empty() { TOMBSTONE_WORD } else { word.as_str() })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Check with a consistency proof whether `new` extends `old`, i.e. only appends words to it.
+/// Trees built with another hasher or other options can't be compared.
+fn consistency_note(old: &MerkleTree<HashAlgorithm>, new: &MerkleTree<HashAlgorithm>) -> Option<String> {
+    let old_root = old.root_hash()?;
+    if old.hasher != new.hasher || old.options != new.options {
+        return None;
+    }
+
+    let (old_size, new_size) = (old.leaf_count(), new.leaf_count());
+    let note = match new.validate_consistency(&old_root, old_size) {
+        Ok(()) => format!("{} -> {} words: extends the previous tree", old_size, new_size),
+        Err(error) => {
+            warn!("Consistency check failed: {}", error);
+            format!("{} -> {} words: does not extend the previous tree", old_size, new_size)
+        }
+    };
+    Some(note)
+}
+
+
+/// Dropping a file on the window builds its chunk tree with the current hasher and draws the top levels
+#[allow(clippy::type_complexity)]
+fn drop_file(
+    mut state: ResMut<GameState>,
+    mut events: EventReader<FileDragAndDrop>,
+    mut commands: Commands,
+    graph_entities: Query<Entity, Or<(With<graph::GraphNode>, With<graph::BranchMarker>, With<graph::NodeTextMarker>)>>
+){
+    let Some(path) = events.read().filter_map(|event| match event {
+        FileDragAndDrop::DroppedFile { path_buf, .. } => Some(path_buf.clone()),
+        _ => None,
+    }).last() else {
+        return;
+    };
+
+    let file = match chunked::build_from_file(&path, chunked::DEFAULT_CHUNK_SIZE, state.hasher, TreeOptions::default()) {
+        Ok(file) => file,
+        Err(error) => {
+            show_error(&mut state, &error);
+            return;
+        }
+    };
+    let tree = match file.top_levels(DROPPED_FILE_LEAVES) {
+        Ok(tree) => tree,
+        Err(error) => {
+            show_error(&mut state, &error);
+            return;
+        }
+    };
+
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+    info!("Dropped {}: {} bytes in {} chunks, root {:?}", name, file.file_size, file.chunk_count(), file.root());
+    state.display_text = format!("{}: {} chunks of {} KiB", name, file.chunk_count(), file.chunk_size / chunked::KIB);
+    state.current_text = tree.words.join(" ");
+    state.toggle_input = false;
+    state.mode = MerkleMode::BuildTree;
+    state.selected_node = None;
+    state.select_node = false;
+    state.proof_selection.clear();
+    state.changed_path = Vec::new();
+    state.consistency = None;
+    state.tree = tree;
+
+    for entity in graph_entities.iter() {
+        commands.entity(entity).despawn();
+    }
+    graph::graph(&state.tree, 600.0, commands, &state.handle);
+}
+
+// add system to track changes in button state based on text input
+#[allow(clippy::type_complexity)]
+fn check_keyboards(
+    mut state: ResMut<GameState>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut commands: Commands,
+    graph_entities: Query<Entity, Or<(With<graph::GraphNode>, With<graph::BranchMarker>, With<graph::NodeTextMarker>)>>
+){
+    if input.just_pressed(KeyCode::Space) && state.toggle_input {
+        state.current_text.push(' ');
+    }
+
+    let backspace_allowed = match state.mode {
+        MerkleMode::RebuildTree => {
+            if let Some(selected_node) = &state.selected_node {
+                !selected_node.hash.is_empty()
+            } else {
+                false
+            }
+        },
+        _ => {
+            !state.current_text.is_empty()
+        }
+    };
+
+    if input.just_pressed(KeyCode::Backspace) && backspace_allowed && state.toggle_input{
+        if state.mode == MerkleMode::RebuildTree {
+            if let Some(selected_node) = &mut state.selected_node {
+                selected_node.hash.pop();
+            }
+        } else {
+            state.current_text.pop();
+
+        }
+    }
+
+    let shift_held = input.pressed(KeyCode::ShiftLeft) || input.pressed(KeyCode::ShiftRight);
+    let caps_lock_on = input.pressed(KeyCode::CapsLock);
+    let ctrl_held = input.pressed(KeyCode::ControlLeft) || input.pressed(KeyCode::ControlRight);
+
+    // Ctrl + S saves the session, Ctrl + O reopens it, neither types a letter
+    if ctrl_held && input.just_pressed(KeyCode::KeyS) {
+        match session::save(&state) {
+            Ok(()) => info!("Session saved to {}", session::SESSION_FILE),
+            Err(error) => show_error(&mut state, &error),
+        }
+        return;
+    }
+    if ctrl_held && input.just_pressed(KeyCode::KeyO) {
+        if let Err(error) = session::open(&mut state) {
+            show_error(&mut state, &error);
+            return;
+        }
+        info!("Session opened from {}", session::SESSION_FILE);
+        // the saved tree replaces whatever is drawn
+        for entity in graph_entities.iter() {
+            commands.entity(entity).despawn();
+        }
+        graph::graph(&state.tree, 600.0, commands, &state.handle);
+        return;
+    }
+
+    // Delete tombstones the selected word, shift + Delete drops it and compacts the tree
+    if input.just_pressed(KeyCode::Delete) && !state.toggle_input && state.mode == MerkleMode::RebuildTree {
+        let selected_word = state.selected_node.as_ref().filter(|node| !node.is_hash).map(|node| node.index);
+        if let Some(index) = selected_word {
+            let policy = if shift_held { tree::RemovePolicy::Compact } else { tree::RemovePolicy::Tombstone };
+            if let Err(error) = state.tree.remove(index, policy) {
+                show_error(&mut state, &error);
+                return;
+            }
+
+            state.current_text = tree_text(&state.tree);
+            state.display_text = state.current_text.clone();
+            state.selected_node = None;
+            state.select_node = false;
+            state.mode = MerkleMode::BuildTree;
+
+            if policy == tree::RemovePolicy::Tombstone {
+                let mut changed_path = vec![(0, index)];
+                changed_path.extend(state.tree.path_to_root(index));
+                state.changed_path = changed_path;
+            } else {
+                // the leaves moved, so the whole tree is drawn again
+                for entity in graph_entities.iter() {
+                    commands.entity(entity).despawn();
+                }
+                graph::graph(&state.tree, 600.0, commands, &state.handle);
+            }
+        }
+        return;
+    }
+
+    // Set current text to previous text
+    if input.just_pressed(KeyCode::Enter) && state.toggle_input {
+
+        match state.mode {
+            MerkleMode::BuildTree => {
+                state.toggle_input = false;
+                state.previous_text = state.display_text.clone();
+                state.display_text = state.current_text.clone();
+        
+        
+                let hash = hasher::hash_word(&state.hasher, &state.display_text.clone(), &TreeOptions::default());
+                 println!("Hash ({}): {}", state.hasher.name(), hash);
+        
+                let clone = state.display_text.clone();
+                let words = clone.split_whitespace().collect::<Vec<&str>>();
+                //println!("Words: {:?}", words);
+        
+                // let hashes = hasher::hash_words(words);
+                // println!("Hashes: {:?}", hashes);
+        
+                let old_words = &state.tree.words;
+                let options = TreeOptions { leaf_order: state.leaf_order, ..Default::default() };
+                // sorted leaves would have to move, so only unsorted trees are grown in place
+                let only_appended = state.tree.root.is_some()
+                    && state.tree.hasher == state.hasher
+                    && state.tree.options == options
+                    && !options.leaf_order.is_sorted()
+                    && words.len() > old_words.len()
+                    && words.iter().zip(old_words.iter()).all(|(new_word, old_word)| {
+                        *new_word == old_word.as_str() || (*new_word == TOMBSTONE_WORD && old_word.is_empty())
+                    });
+
+                let mut changed_path = Vec::new();
+                let built = if only_appended {
+                    // words were only added at the end, push them onto a copy of the current tree
+                    let mut tree = state.tree.clone();
+                    tree.word_to_prove = None;
+                    words[tree.leaf_count()..].iter().try_for_each(|word| {
+                        let index = tree.push(word)?;
+                        changed_path.push((0, index));
+                        changed_path.extend(tree.path_to_root(index));
+                        Ok(())
+                    }).map(|_| tree)
+                } else {
+                    // tombstoned words come back as placeholders, which are tombstoned again after the build
+                    tree::build_tree_with(words, state.hasher, options).and_then(|mut tree| {
+                        let tombstones: Vec<usize> = tree.words.iter().enumerate()
+                            .filter(|(_, word)| word.as_str() == TOMBSTONE_WORD)
+                            .map(|(index, _)| index)
+                            .collect();
+                        tombstones.into_iter().try_for_each(|index| tree.remove(index, tree::RemovePolicy::Tombstone))?;
+                        Ok(tree)
+                    })
+                };
+                let tree = match built {
+                    Ok(tree) => tree,
+                    Err(error) => {
+                        show_error(&mut state, &error);
+                        return;
+                    }
+                };
+        
+                println!("Tree: {:?}", tree);
+        
+                graph::graph(&tree, 600.0, commands, &state.handle);
+        
+                state.changed_path = changed_path;
+                state.consistency = consistency_note(&state.tree, &tree);
+                state.tree = tree;
+        
+                // After building tree, generate the graph
+        
+                // After building tree, we move to displaying it.
+                // Now the algorithm for displaying will be words first, then hashes, as we go down the levels
+                // Then we get more narrower as we move towards the root.
+                // Focus on building with 4 words first, then we expand
+            },
+            MerkleMode::RebuildTree => {
+                println!("Rebuilding Tree");
+                if let Some(selected_node) = &state.selected_node {
+
+                    // if shift is held, instead of rebuilding the tree, we store the new word in the tree object
+                    // and generate the proof, then we indicate if the word belongs in the tree or not
+                    // then when we're rendering we can show the word in red if it doesn't belong in the tree
+                    // else we show it in green if it does belong
+                    // then when we render the nodes, we render the edited word in that node
+                    // so the difference is that we don't rebuild the tree with the new word, we just store the new word
+                    // either way we store the new word in the tree object and generate the proof
+
+                    let index = selected_node.index;
+                    let new_word = selected_node.hash.clone();
+
+                    // if shift is held
+                    if shift_held {
+                        
+                        state.mode = MerkleMode::RebuildTree;
+                        state.toggle_input = false;
+                        // the tree itself is unchanged and still on screen, only earlier proofs are dropped
+                        state.tree.proof = None;
+                        state.tree.multi_proof = None;
+                        state.tree.exclusion_proof = None;
+                        let hash = hasher::hash_word(&state.tree.hasher, &new_word, &state.tree.options);
+                        state.tree.word_to_prove = Some(tree::WordToProve{
+                            index,
+                            word: new_word.clone(),
+                            hash,
+                            display_hash: tree::MerkleTree::format_hash(&hash.to_hex(), 6, 6, "...")
+                        });
+                        return;
+                    }
+
+
+
+                    // only the path from the edited word to the root is rehashed and redrawn
+                    let old_tree = state.tree.clone();
+                    if let Err(error) = state.tree.update(index, &new_word) {
+                        show_error(&mut state, &error);
+                        return;
+                    }
+
+                    state.current_text = tree_text(&state.tree);
+                    state.display_text = state.current_text.clone();
+                    let mut changed_path = vec![(0, index)];
+                    changed_path.extend(state.tree.path_to_root(index));
+                    state.changed_path = changed_path;
+                    state.consistency = consistency_note(&old_tree, &state.tree);
+
+                    state.mode = MerkleMode::BuildTree;
+                    state.toggle_input = false;
+                    println!("Tree: {:?}", state.tree);
+                }
+            },
+            MerkleMode::InclusionProof => {
+                state.mode = MerkleMode::BuildTree;
+                // with the inclusion proof its similar to rebuilding the tree, this time, we don't
+                // change the current text nor the display text, we just rebuild the tree
+                // and highlight the path to the root of the tree, i.e the nodes that are included in the proof
+                // we highlight the nodes included with green and the node with red if its proof failed
+                // Basically we want to show if that node's hash is included in the tree
+            }
+        }
+
+
+        return;
+    }
+
+    if input.just_pressed(KeyCode::KeyI) && !state.toggle_input && state.mode != MerkleMode::InclusionProof {
+        state.toggle_input = true;
+        //state.mode = MerkleMode::BuildTree;
+        // de spawn all tree nodes
+        return;
+    }
+
+    if input.just_pressed(KeyCode::KeyL) {
+        println!("Toggle State: {}, Current Text: {}, Display Text: {}, Previous Text: {}", state.toggle_input,state.current_text, state.display_text, state.previous_text);
+        println!("Word to Prove: {:?}", state.tree.word_to_prove);
+    }
+
+    let letters = [
+        (KeyCode::KeyA, 'a'), (KeyCode::KeyB, 'b'), (KeyCode::KeyC, 'c'), (KeyCode::KeyD, 'd'),
+        (KeyCode::KeyE, 'e'), (KeyCode::KeyF, 'f'), (KeyCode::KeyG, 'g'), (KeyCode::KeyH, 'h'),
+        (KeyCode::KeyI, 'i'), (KeyCode::KeyJ, 'j'), (KeyCode::KeyK, 'k'), (KeyCode::KeyL, 'l'),
+        (KeyCode::KeyM, 'm'), (KeyCode::KeyN, 'n'), (KeyCode::KeyO, 'o'), (KeyCode::KeyP, 'p'),
+        (KeyCode::KeyQ, 'q'), (KeyCode::KeyR, 'r'), (KeyCode::KeyS, 's'), (KeyCode::KeyT, 't'),
+        (KeyCode::KeyU, 'u'), (KeyCode::KeyV, 'v'), (KeyCode::KeyW, 'w'), (KeyCode::KeyX, 'x'),
+        (KeyCode::KeyY, 'y'), (KeyCode::KeyZ, 'z'),
+    ];
+
+
+
+    for (key_code, letter) in letters.iter() {
+        if input.just_pressed(*key_code) && state.toggle_input {
+            let mut letter_to_add = *letter;
+
+            if shift_held || caps_lock_on {
+                letter_to_add = letter_to_add.to_ascii_uppercase();
+            }
+
+            // If we are in rebuild tree mode, we add the letter to the selected node
+            if state.mode == MerkleMode::RebuildTree {
+                if let Some(selected_node) = &mut state.selected_node {
+                    selected_node.hash.push(letter_to_add);
+                }
+                continue;
+            }
+            state.current_text.push(letter_to_add);
+        }
+    }
+
+
+}
+
+fn update_loop_text(
+    state: ResMut<GameState>, 
+    mut query: Query<(&mut Transform, &mut Text), With<TextBarTextMarker>> 
+) {
+    for (_transform, mut text) in query.iter_mut() {
+        if state.toggle_input {
+            // if mode is rebuild tree, then display the selected node text
+            // else display the current text
+
+            //println!("Mode in update loop text: {:?}", state.mode);
+            if state.mode == MerkleMode::RebuildTree || state.mode == MerkleMode::InclusionProof {
+                text.sections[0].value = state.selected_node.as_ref().map_or("".to_string(), |node| node.hash.clone());
+                text.sections[0].style.color = Color::WHITE;
+                continue;
+            }
+
+            text.sections[0].value = state.current_text.clone(); 
+            text.sections[0].style.color = Color::WHITE;       
+        } else {
+            text.sections[0].value = state.display_text.clone();
+            text.sections[0].style.color = Color::BLACK;      
+        }
+    }
+}
+
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn update_loop_tree(
+    mut state: ResMut<GameState>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    mut query: Query<(Entity, &Transform, Option<&mut Sprite>, Option<&graph::GraphNode>), Or<(With<graph::GraphNode>, With<graph::BranchMarker>, With<graph::NodeTextMarker>)>>, // Add optional Sprite
+    mut commands: Commands,
+    mut query_text: Query<(Entity, &mut Text, &graph::NodeTextMarker)>, 
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    input: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>
+) {
+    // an edited word keeps the tree on screen, only its path is redrawn
+    if state.toggle_input && state.mode != MerkleMode::RebuildTree {
+        for (entity, _transform, _sprite,_node) in query.iter_mut() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let mut mouse_clicked = false;
+
+    if let Some(position) = q_windows.single().cursor_position() {
+        let (camera, camera_transform) = q_camera.single();
+
+        let window_size = Vec2::new(q_windows.single().width(), q_windows.single().height());
+
+        // Convert screen position (origin is top-left) to normalized device coordinates (NDC) (-1 to +1 range)
+        let mut ndc = (position / window_size) * 2.0 - Vec2::ONE;
+
+        ndc.y = -ndc.y;
+
+        //  NDC to world space coordinates
+        if let Some(world_position) = camera.ndc_to_world(camera_transform, ndc.extend(-1.0)) {
+            state.mouse_position = (world_position.x, world_position.y);
+        }
+    }
+
+    if input.just_pressed(MouseButton::Left){
+        println!("Mouse Clicked at: {:?}", state.mouse_position);
+        mouse_clicked = true;
+    }
+
+    let mut clicked_on_node = false;
+    let shift_held = keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight);
+
+
+    // Loop through node entities and check if the mouse is within the node bounds
+    for (_entity, transform,  sprite_option, node_option) in query.iter_mut() {
+        if let Some(node) = node_option {
+            if let Some(mut sprite) = sprite_option {
+
+                if node.is_hash{
+                    // based on the mode, we can highlight the nodes that are part of the inclusion proof
+                    // so if mode is inclusion proof, we highlight the nodes that are part of the proof
+                    if state.mode == MerkleMode::InclusionProof {
+                        // a multiproof from shift-clicking several words replaces the single proof
+                        let sibling_positions = match (&state.tree.exclusion_proof, &state.tree.multi_proof, &state.tree.proof) {
+                            // both neighbours of an excluded word are proven
+                            (Some(exclusion_proof), _, _) => Some([&exclusion_proof.left, &exclusion_proof.right].into_iter()
+                                .flatten()
+                                .flat_map(|neighbour| neighbour.proof.sibling_positions())
+                                .collect()),
+                            (None, Some(multi_proof), _) => Some(multi_proof.sibling_positions()),
+                            (None, None, Some(proof)) => Some(proof.sibling_positions()),
+                            (None, None, None) => None,
+                        };
+                        if sibling_positions.is_some() {
+                            if let Some(sibling_positions) = sibling_positions {
+                                // each proof step names the level of its sibling, the index follows from the leaf index
+                                for (sibling_level, sibling_index) in sibling_positions {
+                                    if sibling_level == node.level && sibling_index == node.index {
+                                        sprite.color = Color::srgb(0.2, 0.8, 0.2); // Green highlight for nodes in the inclusion proof
+                                    }
+                                }
+                            }
+                            // Now based on the proof steps, we can change the color of the nodes that are part of the proof to green and their text to black
+                            // and if the proof is valid, we change the color of the word node to green and the text to black else we change the color to red
+                        }
+
+                        if let Some(exclusion_proof) = &state.tree.exclusion_proof {
+                            for neighbour in [&exclusion_proof.left, &exclusion_proof.right].into_iter().flatten() {
+                                if node.level == 1 && node.index == neighbour.proof.leaf_index {
+                                    sprite.color = Color::srgb(0.2, 0.4, 0.9); // Blue for the leaves either side of the excluded word
+                                }
+                            }
+                        }
+                    }else {
+                        sprite.color = Color::BLACK;
+                    }
+
+                    if let Some(word_to_prove) = &state.tree.word_to_prove {
+                        if word_to_prove.index == node.index && node.level == 1 {
+                            if let Some((_, mut text, _)) = query_text.iter_mut().find(|(_, _, marker)| marker.node_index == node.index && marker.node_level == node.level) {
+                                text.sections[0].value = word_to_prove.display_hash.clone();
+                            }
+                        }
+                    }
+                
+                    continue;
+                }
+
+                if let Some(word_to_prove) = &state.tree.word_to_prove {
+                    if word_to_prove.index == node.index && node.level == 0 {
+                        // Find the corresponding text entity by using NodeTextMarker
+                        if let Some((_, mut text, _)) = query_text.iter_mut().find(|(_, _, marker)| marker.node_index == node.index && marker.node_level == node.level) {
+                            //println!("Updating Text to Word to Prove: {:?}, Node: {:?}", text.sections[0].value, node);
+                            text.sections[0].value = word_to_prove.word.clone(); // Update the text to the word to prove
+                        }
+                    }
+                }
+
+                let node_position = transform.translation;
+                let node_size = transform.scale;
+
+                let tolerance = 5.0;
+                let half_width = (node_size.x / 2.0) + tolerance;
+                let half_height = (node_size.y / 2.0) + tolerance;
+
+                let within_x_bounds = state.mouse_position.0 >= node_position.x - half_width &&
+                state.mouse_position.0 <= node_position.x + half_width;
+                let within_y_bounds = state.mouse_position.1 >= node_position.y - half_height &&
+                state.mouse_position.1 <= node_position.y + half_height;
+
+                let within_bounds = within_x_bounds && within_y_bounds;
+
+
+                if within_bounds {
+                    //println!("Mouse is hovering over node with text: {} at position: {:?}", node.hash, node_position);
+                    
+                    // If no node is selected or this node is not the selected one, highlight on hover
+                    if state.selected_node.is_none() || 
+                    state.selected_node.as_ref().is_none_or(|selected_node| selected_node.hash != node.hash) {
+                        sprite.color = Color::srgb(0.8, 0.8, 0.2); // Yellow highlight color on 
+                    }
+
+                    if mouse_clicked && shift_held && state.mode == MerkleMode::InclusionProof {
+                        // add or remove the word from the combined proof
+                        if let Some(position) = state.proof_selection.iter().position(|index| *index == node.index) {
+                            state.proof_selection.remove(position);
+                        } else {
+                            state.proof_selection.push(node.index);
+                        }
+                        clicked_on_node = true;
+                        state.tree.proof = None;
+                        state.tree.multi_proof = None;
+                        state.tree.exclusion_proof = None;
+                        let selection = state.proof_selection.clone();
+                        if !selection.is_empty() {
+                            if let Err(error) = state.tree.multi_proof(&selection) {
+                                show_error(&mut state, &error);
+                            }
+                        }
+                    } else if mouse_clicked {
+                        state.selected_node = Some(node.clone());
+                        state.select_node = true;
+                        sprite.color = Color::srgb(0.2, 0.2, 0.2); // Dark gray color on click
+                        clicked_on_node = true;
+                        state.mode = MerkleMode::RebuildTree;
+                        state.tree.proof = None;
+                        state.tree.multi_proof = None;
+                        state.tree.exclusion_proof = None;
+                        state.proof_selection.clear();
+                        println!("Mode: {:?}", state.mode);
+
+                    }
+                } else if mouse_clicked && !clicked_on_node {
+                    // Clicked outside, deselect the node
+                    if state.mode == MerkleMode::InclusionProof{
+                        continue;
+                    }
+                    state.select_node = false;
+                    if let Some(selected_node) = &state.selected_node {
+                        if selected_node.hash == node.hash {
+                            sprite.color = Color::BLACK;
+                            state.selected_node = None;
+                        }
+                    }
+                    state.mode = MerkleMode::BuildTree;
+
+                } else if !state.select_node || 
+                state.selected_node.as_ref().is_none_or(|selected_node| selected_node.hash != node.hash) {
+                   
+                    sprite.color = Color::BLACK;
+                }
+
+                // words in the multiproof stay marked as selected
+                if state.mode == MerkleMode::InclusionProof && state.tree.multi_proof.is_some() && state.proof_selection.contains(&node.index) {
+                    sprite.color = Color::srgb(0.2, 0.2, 0.2);
+                }
+
+                if let Some(word_to_prove) = &state.tree.word_to_prove {
+                    if word_to_prove.index == node.index && node.level == 0 {
+                        if let Some(proof) = &state.tree.proof  {
+                            if proof.is_valid {
+                                sprite.color = Color::srgb(0.2, 0.8, 0.2);
+                            } else {
+                                sprite.color = Color::srgb(0.8, 0.2, 0.2);
+
+                            }
+                        }
+                    }
+                }
+
+
+
+                // highlight selected node
+            } else {
+                println!("Entity without a sprite, skipping bounds check");
+            }
+
+
+        }
+    }
+}
+
+                // if state.mode == MerkleMode::InclusionProof {
+                //     if state.tree.proof.is_some() {
+                //         if let Some(proof) = &state.tree.proof {
+
+                //             if let Some(node_sel) = &state.selected_node {
+                //                 if node.index == node_sel.index {
+                //                     if proof.is_valid {
+                //                         sprite.color = Color::srgb(0.2, 0.8, 0.2); // Green highlight for nodes in the inclusion proof
+                //                     } else {
+                //                         sprite.color = Color::srgb(0.8, 0.2, 0.2); // Red highlight for nodes in the inclusion proof
+                //                     }
+                //                 }
+                //             }
+                //             sprite.color = Color::srgb(0.2, 0.8, 0.2); // Green highlight for nodes in the inclusion proof
+
+                //         }
+                //         // Now based on proof_links, we can change the color of the nodes that are part of the proof to green and their text to black
+                //         // and if the proof is valid, we change the color of the word node to green and the text to black else we change the color to red
+                //     }
+                // }else {
+                //     sprite.color = Color::BLACK;
+                // }
+
+
+
+fn update_proof_outcome(
+    state: Res<GameState>,
+    node_query: Query<(&Transform, &graph::GraphNode), Without<ProofOutcomeMarker>>,
+    mut outcome_query: Query<(&mut Transform, &mut Text), With<ProofOutcomeMarker>>,
+) {
+    // a multiproof is labelled above its first word, an exclusion proof above the excluded word
+    let exclusion = state.tree.exclusion_proof.as_ref().zip(state.tree.word_to_prove.as_ref());
+    let outcome = match (exclusion, &state.tree.multi_proof) {
+        (Some((exclusion_proof, word_to_prove)), _) => exclusion_proof.outcome.map(|outcome| (word_to_prove.index, outcome)),
+        (None, Some(multi_proof)) => multi_proof.outcome.zip(multi_proof.leaf_indices.first().copied()).map(|(outcome, leaf_index)| (leaf_index, outcome)),
+        (None, None) => state.tree.proof.as_ref().and_then(|proof| proof.outcome.map(|outcome| (proof.leaf_index, outcome))),
+    };
+
+    for (mut transform, mut text) in outcome_query.iter_mut() {
+        let word_node = outcome.and_then(|(leaf_index, _)| {
+            node_query.iter().find(|(_, node)| !node.is_hash && node.index == leaf_index)
+        });
+
+        match (outcome, word_node) {
+            (Some((_, outcome)), Some((node_transform, _))) if !state.toggle_input => {
+                // just above the word node
+                let node_position = node_transform.translation;
+                transform.translation = Vec3::new(node_position.x, node_position.y + node_transform.scale.y / 2.0 + 12.0, 10.0);
+                text.sections[0].value = if exclusion.is_some() && outcome.is_valid() {
+                    "not in tree".to_string()
+                } else {
+                    outcome.reason().to_string()
+                };
+                text.sections[0].style.color = if outcome.is_valid() {
+                    Color::srgb(0.2, 0.8, 0.2)
+                } else {
+                    Color::srgb(0.8, 0.2, 0.2)
+                };
+            }
+            _ => {
+                text.sections[0].value = "".to_string();
+            }
+        }
+    }
+}
+
+/// Redraw the nodes on `changed_path` with their new hashes and start them pulsing
+fn apply_changed_path(
+    mut state: ResMut<GameState>,
+    mut commands: Commands,
+    mut node_query: Query<(Entity, &mut graph::GraphNode)>,
+    mut text_query: Query<(&mut Text, &graph::NodeTextMarker)>,
+) {
+    if state.changed_path.is_empty() {
+        return;
+    }
+
+    // a freshly drawn graph only shows up a frame later, keep the path until its nodes exist
+    let mut found = false;
+    for (entity, mut node) in node_query.iter_mut() {
+        if !state.changed_path.contains(&(node.level, node.index)) {
+            continue;
+        }
+
+        let display_text = if node.is_hash {
+            let Some(tree_node) = state.tree.nodes.get(&node.level).and_then(|nodes| nodes.get(node.index)) else {
+                continue;
+            };
+            node.hash = tree_node.hash.to_hex();
+            MerkleTree::format_hash(&node.hash, 6, 6, "...")
+        } else {
+            node.hash = state.tree.words.get(node.index).cloned().unwrap_or_default();
+            node.hash.clone()
+        };
+
+        if let Some((mut text, _)) = text_query.iter_mut().find(|(_, marker)| marker.node_level == node.level && marker.node_index == node.index) {
+            text.sections[0].value = display_text;
+        }
+        commands.entity(entity).insert(PathPulse { remaining: PATH_PULSE_SECONDS });
+        found = true;
+    }
+
+    if found {
+        state.changed_path.clear();
+    }
+}
+
+fn animate_path_pulse(time: Res<Time>, mut commands: Commands, mut query: Query<(Entity, &mut Sprite, &mut PathPulse)>) {
+    for (entity, mut sprite, mut pulse) in query.iter_mut() {
+        pulse.remaining -= time.delta_seconds();
+        if pulse.remaining <= 0.0 {
+            sprite.color = Color::BLACK;
+            commands.entity(entity).remove::<PathPulse>();
+            continue;
+        }
+        let fade = pulse.remaining / PATH_PULSE_SECONDS;
+        sprite.color = Color::srgb(0.9 * fade, 0.5 * fade, 0.1 * fade);
+    }
+}
+
+fn update_consistency_text(state: Res<GameState>, mut query: Query<&mut Text, With<ConsistencyTextMarker>>) {
+    for mut text in query.iter_mut() {
+        let note = if state.toggle_input { None } else { state.consistency.as_ref() };
+        text.sections[0].value = note.cloned().unwrap_or_default();
+    }
+}
+
+/// Left and right arrows pan along trees too wide for the window, the text bar moves along to stay in view
+#[allow(clippy::type_complexity)]
+fn pan_camera(
+    state: Res<GameState>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+    mut text_bars: Query<&mut Transform, (Or<(With<TextBarMarker>, With<TextBarTextMarker>)>, Without<Camera>)>,
+){
+    let Some(window) = window_query.iter().next() else {
+        return;
+    };
+    let limit = ((graph::layout_width(state.tree.leaf_count()) - window.width()) / 2.0).max(0.0);
+
+    let mut direction = 0.0;
+    if keys.pressed(KeyCode::ArrowLeft) {
+        direction -= 1.0;
+    }
+    if keys.pressed(KeyCode::ArrowRight) {
+        direction += 1.0;
+    }
+
+    for mut camera in cameras.iter_mut() {
+        // a narrower tree pulls the camera back within its edges
+        camera.translation.x = (camera.translation.x + direction * PAN_SPEED * time.delta_seconds()).clamp(-limit, limit);
+        for mut text_bar in text_bars.iter_mut() {
+            text_bar.translation.x = camera.translation.x;
+        }
+    }
+}
+
+fn sprite_update(state: ResMut<GameState>, mut query: Query<&mut Sprite, With<TextBarMarker>>, window_query: Query<&Window, With<PrimaryWindow>>){
+    let window = window_query.iter().next().unwrap();
+    for mut sprite in query.iter_mut(){
+        if state.toggle_input {
+            sprite.color = Color::srgba(0.12, 0.12, 0.12, 0.77);
+            sprite.custom_size = Some(Vec2::new(window.width(), window.height()));
+        } else {
+            sprite.color = Color::NONE;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn text_bar_update(state: Res<GameState>, mut param_set: ParamSet<(
+    Query<(&Children, &Sprite), With<TextBarMarker>>,
+    Query<&mut Sprite>,
+)>){
+
+    let mut children_to_update = vec![];
+
+    {
+        let parent_query = param_set.p0();
+        for (children, _) in parent_query.iter() {
+            for &child in children.iter() {
+                children_to_update.push(child);
+            }
+        }
+    }
+
+    let mut child_query = param_set.p1();
+    for child in children_to_update {
+        if let Ok(mut child_sprite) = child_query.get_mut(child) {
+            if state.toggle_input {
+                child_sprite.color = Color::srgba(1.0, 0.12, 0.12, 1.0);
+            } else {
+                child_sprite.color = Color::NONE;
+            }
+        }
+    }
+}
+
+
+
+// How to build the merkle tree? First understand how the process works.
+// What makes up the tree, what do we need to break down, word by word, character by character?
+// How do we build the tree from the ground up?  https://en.wikipedia.org/wiki/Merkle_tree
+// Then do select on click and highlight the path to the root of the tree
+// Then when we click on a node, we can see the hash of the node, and the hash of the children
+// When a node is highlighted, we can edit the text of the node and see the hash update in real time
+// We can also do inclusion proofs, where we can see the path to the root of the tree, if the edited node
+// belongs to the tree or not
+// Basically verify a node, with specific hashes we can see from its path to the root of the tree
+
+
+// Now based on proof_links, we can change the color of the nodes that are part of the proof to green and their text to black
+// and if the proof is valid, we change the color of the word node to green and the text to black else we change the color to red
+
+
+
+// For the proof out of place, we don't rebuild the new word with the tree, we simply store the new word in the tree object along
+// with the index of the word it is to replace, then we generate the proof as always and indicate if the word belongs in that tree
+// Then when we're rendering we can show the word in red if it doesn't belong in the tree, else we show it in green if it does belong
+// Then whe we render the nodes, we render the edited word in that node
\ No newline at end of file
@@ -0,0 +1,303 @@
+use std::{fmt, fs, io};
+
+use merkle::{codec::{CodecError, TreeDocument}, hasher::{self, HashAlgorithm, MerkleHasher}, tree::{self, LeafOrder, MerkleTree}};
+use serde::{Deserialize, Serialize};
+
+use super::{graph, GameState, MerkleMode};
+
+/// Format version of saved sessions, bumped whenever the layout changes
+pub const SESSION_VERSION: u8 = 1;
+/// File written by Ctrl + S and read back by Ctrl + O, in the working directory
+pub const SESSION_FILE: &str = "merkle-session.json";
+
+/// Everything needed to pick a demo up where it was left: the tree, the settings for the next build
+/// and edits that were typed but not applied yet.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+    pub version: u8,
+    pub mode: MerkleMode,
+    /// Hasher used for the next build, the tree keeps its own
+    pub hasher: String,
+    pub leaf_order: LeafOrder,
+    pub toggle_input: bool,
+    pub current_text: String,
+    pub display_text: String,
+    /// `None` before the first build
+    pub tree: Option<TreeDocument>,
+    /// Node being edited in rebuild mode, with the text typed into it so far
+    pub selected_node: Option<SelectedNode>,
+    /// Edited word checked against the tree without rebuilding it
+    pub word_to_prove: Option<PendingWord>,
+    pub proof_selection: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelectedNode {
+    pub text: String,
+    pub level: u32,
+    pub parent_index: (u32, u32),
+    pub index: usize,
+    pub position: (f32, f32),
+    pub is_hash: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PendingWord {
+    pub index: usize,
+    pub word: String,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Json(String),
+    UnsupportedVersion(u8),
+    UnknownHasher(String),
+    Tree(CodecError),
+    /// A saved index points past the words of the saved tree
+    IndexOutOfRange(usize),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Io(error) => write!(f, "session file: {}", error),
+            SessionError::Json(error) => write!(f, "malformed session: {}", error),
+            SessionError::UnsupportedVersion(version) => write!(f, "session version {} is not supported", version),
+            SessionError::UnknownHasher(name) => write!(f, "unknown hasher {}", name),
+            SessionError::Tree(error) => write!(f, "saved tree: {}", error),
+            SessionError::IndexOutOfRange(index) => write!(f, "saved word index {} is out of range", index),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> Self {
+        SessionError::Io(error)
+    }
+}
+
+impl From<CodecError> for SessionError {
+    fn from(error: CodecError) -> Self {
+        SessionError::Tree(error)
+    }
+}
+
+impl Session {
+    pub fn from_state(state: &GameState) -> Session {
+        Session {
+            version: SESSION_VERSION,
+            mode: state.mode.clone(),
+            hasher: state.hasher.name().to_string(),
+            leaf_order: state.leaf_order,
+            toggle_input: state.toggle_input,
+            current_text: state.current_text.clone(),
+            display_text: state.display_text.clone(),
+            tree: state.tree.root.is_some().then(|| TreeDocument::from_tree(&state.tree)),
+            selected_node: state.selected_node.as_ref().map(|node| SelectedNode {
+                text: node.hash.clone(),
+                level: node.level,
+                parent_index: node.parent_index,
+                index: node.index,
+                position: node.position,
+                is_hash: node.is_hash,
+            }),
+            word_to_prove: state.tree.word_to_prove.as_ref().map(|word| PendingWord { index: word.index, word: word.word.clone() }),
+            proof_selection: state.proof_selection.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a session always serializes")
+    }
+
+    pub fn from_json(json: &str) -> Result<Session, SessionError> {
+        // the version is read on its own first, so a newer layout is reported as such
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u8,
+        }
+        let versioned: Versioned = serde_json::from_str(json).map_err(|error| SessionError::Json(error.to_string()))?;
+        if versioned.version != SESSION_VERSION {
+            return Err(SessionError::UnsupportedVersion(versioned.version));
+        }
+        serde_json::from_str(json).map_err(|error| SessionError::Json(error.to_string()))
+    }
+
+    /// Check the session and write it into `state`, which is left untouched if anything is wrong.
+    /// The caller still has to redraw the graph for the restored tree.
+    pub fn restore(self, state: &mut GameState) -> Result<(), SessionError> {
+        let hasher = HashAlgorithm::from_name(&self.hasher).ok_or_else(|| SessionError::UnknownHasher(self.hasher.clone()))?;
+        let mut tree = match self.tree {
+            Some(document) => {
+                let tree_hasher = HashAlgorithm::from_name(&document.hasher).ok_or_else(|| SessionError::UnknownHasher(document.hasher.clone()))?;
+                document.into_tree(tree_hasher)?
+            },
+            None => MerkleTree::default(),
+        };
+
+        let leaf_count = tree.leaf_count();
+        let out_of_range = self.selected_node.iter().filter(|node| !node.is_hash).map(|node| node.index)
+            .chain(self.word_to_prove.iter().map(|word| word.index))
+            .chain(self.proof_selection.iter().copied())
+            .find(|index| *index >= leaf_count);
+        if let Some(index) = out_of_range {
+            return Err(SessionError::IndexOutOfRange(index));
+        }
+
+        if let Some(word) = self.word_to_prove {
+            let hash = hasher::hash_word(&tree.hasher, &word.word, &tree.options);
+            tree.word_to_prove = Some(tree::WordToProve {
+                index: word.index,
+                word: word.word,
+                hash,
+                display_hash: MerkleTree::format_hash(&hash.to_hex(), 6, 6, "..."),
+            });
+        }
+        if self.mode == MerkleMode::InclusionProof && !self.proof_selection.is_empty() {
+            // proofs are not saved, the multiproof for the selected words is simply made again
+            tree.multi_proof(&self.proof_selection).map_err(|error| SessionError::Tree(CodecError::Tree(error)))?;
+        }
+
+        state.mode = self.mode;
+        state.hasher = hasher;
+        state.leaf_order = self.leaf_order;
+        state.toggle_input = self.toggle_input;
+        state.current_text = self.current_text;
+        state.display_text = self.display_text;
+        state.select_node = self.selected_node.is_some();
+        state.selected_node = self.selected_node.map(|node| graph::GraphNode {
+            hash: node.text,
+            level: node.level,
+            parent_index: node.parent_index,
+            index: node.index,
+            position: node.position,
+            is_hash: node.is_hash,
+        });
+        state.proof_selection = self.proof_selection;
+        state.consistency = None;
+        state.changed_path = Vec::new();
+        state.tree = tree;
+        Ok(())
+    }
+}
+
+/// Write the current state to `SESSION_FILE`
+pub fn save(state: &GameState) -> Result<(), SessionError> {
+    fs::write(SESSION_FILE, Session::from_state(state).to_json())?;
+    Ok(())
+}
+
+/// Read `SESSION_FILE` back into `state`
+pub fn open(state: &mut GameState) -> Result<(), SessionError> {
+    let json = fs::read_to_string(SESSION_FILE)?;
+    Session::from_json(&json)?.restore(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use merkle::tree::TreeOptions;
+
+    use super::*;
+
+    /// A state in proof mode, with a word being edited and two words picked for a multiproof
+    fn state() -> GameState {
+        let options = TreeOptions { leaf_order: LeafOrder::ByValue, ..Default::default() };
+        let mut tree = tree::build_tree_with(["a", "b", "c", "d", "e"], HashAlgorithm::Sha256, options).unwrap();
+        let hash = hasher::hash_word(&tree.hasher, "x", &tree.options);
+        tree.word_to_prove = Some(tree::WordToProve { index: 1, word: "x".to_string(), hash, display_hash: String::new() });
+        GameState {
+            mode: MerkleMode::InclusionProof,
+            hasher: HashAlgorithm::Blake3,
+            leaf_order: LeafOrder::ByHash,
+            toggle_input: false,
+            current_text: "a b c d e".to_string(),
+            display_text: "a b c d e".to_string(),
+            tree,
+            select_node: true,
+            selected_node: Some(graph::GraphNode { hash: "c".to_string(), level: 0, parent_index: (1, 2), index: 2, position: (10.0, -20.0), is_hash: false }),
+            proof_selection: vec![0, 3],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sessions_round_trip() {
+        let saved = state();
+        let session = Session::from_json(&Session::from_state(&saved).to_json()).unwrap();
+        let mut restored = GameState::default();
+        session.restore(&mut restored).unwrap();
+
+        assert_eq!(restored.mode, MerkleMode::InclusionProof);
+        assert_eq!(restored.hasher, HashAlgorithm::Blake3);
+        assert_eq!(restored.leaf_order, LeafOrder::ByHash);
+        assert!(!restored.toggle_input);
+        assert_eq!(restored.current_text, saved.current_text);
+        assert_eq!(restored.display_text, saved.display_text);
+        assert_eq!(restored.tree.root_hash(), saved.tree.root_hash());
+        assert_eq!(restored.tree.hasher, HashAlgorithm::Sha256);
+        assert_eq!(restored.tree.options, saved.tree.options);
+        assert_eq!(restored.tree.words, saved.tree.words);
+        assert!(restored.select_node);
+        let node = restored.selected_node.unwrap();
+        assert_eq!((node.hash.as_str(), node.level, node.parent_index, node.index, node.position, node.is_hash), ("c", 0, (1, 2), 2, (10.0, -20.0), false));
+        let word = restored.tree.word_to_prove.unwrap();
+        assert_eq!((word.index, word.word.as_str(), word.hash), (1, "x", saved.tree.word_to_prove.unwrap().hash));
+        assert_eq!(restored.proof_selection, [0, 3]);
+        assert_eq!(restored.tree.multi_proof.unwrap().leaf_indices, [0, 3]);
+    }
+
+    #[test]
+    fn sessions_without_a_tree_round_trip() {
+        let session = Session::from_json(&Session::from_state(&GameState::default()).to_json()).unwrap();
+        assert!(session.tree.is_none());
+        let mut restored = state();
+        session.restore(&mut restored).unwrap();
+        assert_eq!(restored.tree.root_hash(), None);
+        assert_eq!(restored.mode, MerkleMode::BuildTree);
+    }
+
+    /// Restoring `session` must fail with `IndexOutOfRange(index)` and leave the state as it was
+    fn assert_rejected(session: Session, index: usize) {
+        let mut state = GameState::default();
+        assert!(matches!(session.restore(&mut state), Err(SessionError::IndexOutOfRange(found)) if found == index));
+        assert_eq!(state.mode, MerkleMode::BuildTree);
+        assert_eq!(state.tree.root_hash(), None);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let mut session = Session::from_state(&state());
+        session.selected_node.as_mut().unwrap().index = 5;
+        assert_rejected(session, 5);
+
+        let mut session = Session::from_state(&state());
+        session.word_to_prove.as_mut().unwrap().index = 7;
+        assert_rejected(session, 7);
+
+        let mut session = Session::from_state(&state());
+        session.proof_selection.push(9);
+        assert_rejected(session, 9);
+
+        // hash nodes are indexed within their level, not by word
+        let mut session = Session::from_state(&state());
+        let node = session.selected_node.as_mut().unwrap();
+        (node.is_hash, node.level, node.index) = (true, 1, 5);
+        assert!(session.restore(&mut GameState::default()).is_ok());
+    }
+
+    #[test]
+    fn other_versions_and_hashers_are_rejected() {
+        let json = Session::from_state(&state()).to_json().replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(matches!(Session::from_json(&json), Err(SessionError::UnsupportedVersion(2))));
+
+        let mut session = Session::from_state(&state());
+        session.hasher = "MD5".to_string();
+        assert!(matches!(session.restore(&mut GameState::default()), Err(SessionError::UnknownHasher(name)) if name == "MD5"));
+    }
+}
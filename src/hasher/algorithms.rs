@@ -0,0 +1,134 @@
+use std::io::{self, Read};
+
+use sha2::Digest as _;
+
+use super::{Digest, MerkleHasher};
+
+/// Keccak-256, as used by Ethereum
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Keccak256;
+
+/// SHA-256, as used by Bitcoin
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Sha256;
+
+/// SHA-256 applied twice, Bitcoin's hash for txids and its block Merkle tree
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DoubleSha256;
+
+/// NIST SHA3-256. Differs from Keccak-256 only in padding.
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Sha3_256;
+
+/// BLAKE2b with a 32-byte output
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Blake2b256;
+
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Blake3;
+
+impl MerkleHasher for Keccak256 {
+    fn name(&self) -> &'static str {
+        "Keccak-256"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        let mut result = [0u8; 32];
+        keccak_hash::keccak_256(data, &mut result);
+        Digest(result)
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        // the sha3 crate's Keccak-256 gives the same digest and takes its input in pieces
+        Ok(Digest(digest_reader::<sha3::Keccak256>(reader)?))
+    }
+}
+
+impl MerkleHasher for Sha256 {
+    fn name(&self) -> &'static str {
+        "SHA-256"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        Digest(sha2::Sha256::digest(data).into())
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        Ok(Digest(digest_reader::<sha2::Sha256>(reader)?))
+    }
+}
+
+impl MerkleHasher for DoubleSha256 {
+    fn name(&self) -> &'static str {
+        "SHA-256d"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        Digest(sha2::Sha256::digest(sha2::Sha256::digest(data)).into())
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        Ok(Digest(sha2::Sha256::digest(digest_reader::<sha2::Sha256>(reader)?).into()))
+    }
+}
+
+impl MerkleHasher for Sha3_256 {
+    fn name(&self) -> &'static str {
+        "SHA3-256"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        Digest(sha3::Sha3_256::digest(data).into())
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        Ok(Digest(digest_reader::<sha3::Sha3_256>(reader)?))
+    }
+}
+
+impl MerkleHasher for Blake2b256 {
+    fn name(&self) -> &'static str {
+        "BLAKE2b"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        Digest(blake2::Blake2b::<blake2::digest::consts::U32>::digest(data).into())
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        Ok(Digest(digest_reader::<blake2::Blake2b::<blake2::digest::consts::U32>>(reader)?))
+    }
+}
+
+impl MerkleHasher for Blake3 {
+    fn name(&self) -> &'static str {
+        "BLAKE3"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        Digest(blake3::hash(data).into())
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        let mut hasher = blake3::Hasher::new();
+        io::copy(reader, &mut hasher)?;
+        Ok(Digest(hasher.finalize().into()))
+    }
+}
+
+/// Feed everything `reader` yields to a `D` in pieces
+fn digest_reader<D>(reader: &mut dyn Read) -> io::Result<[u8; 32]>
+where
+    D: sha2::Digest + io::Write,
+    sha2::digest::Output<D>: Into<[u8; 32]>,
+{
+    let mut hasher = D::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
@@ -1,28 +1,180 @@
-use keccak_hash;
+mod algorithms;
+mod digest;
+mod poseidon;
 
+use std::io::{self, Read};
 
+use serde::{Deserialize, Serialize};
 
-pub fn keccak_256(word: &str) -> String {
-    let mut result = [0u8; 32];
-    let bytes = word.as_bytes();
+pub use algorithms::{Blake2b256, Blake3, DoubleSha256, Keccak256, Sha256, Sha3_256};
+pub use digest::Digest;
+pub use poseidon::PoseidonTest;
 
-    keccak_hash::keccak_256(bytes,&mut result);
+use crate::tree::TreeOptions;
 
-    let hash = result.iter().map(|b| format!("{:02x}", b)).collect::<String>();
-    return hash;
+/// Sibling used for an odd node under `OddNodeRule::PadEmpty`
+pub const EMPTY_HASH: Digest = Digest::ZERO;
+
+/// Prefix of a leaf's data when `TreeOptions::domain_separation` is on
+pub const LEAF_PREFIX: u8 = 0x00;
+
+/// Prefix of an internal node's children when `TreeOptions::domain_separation` is on
+pub const NODE_PREFIX: u8 = 0x01;
+
+/// How two child digests are fed to the hasher when they are combined
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PairEncoding {
+    /// `hash(left || right)` over the raw 32-byte digests, as every other Merkle implementation does
+    #[default]
+    Bytes,
+    /// `hash(hex(left) || hex(right))` over the hex text, the scheme this project first shipped with.
+    /// Only useful to reproduce roots computed by older versions.
+    LegacyHex,
+}
+
+/// A hash function a `MerkleTree` can be built with
+pub trait MerkleHasher {
+    /// Human readable name, shown in the GUI
+    fn name(&self) -> &'static str;
+
+    fn hash(&self, data: &[u8]) -> Digest;
+
+    /// Hash everything `reader` yields, the same as `hash` over all of it. Hashers that can't take
+    /// their input in pieces read it whole first.
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(self.hash(&data))
+    }
+}
+
+/// A hasher chosen at runtime, for callers like the GUI that switch between algorithms
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Keccak256,
+    Sha256,
+    DoubleSha256,
+    Sha3_256,
+    Blake2b256,
+    Blake3,
+    PoseidonTest,
 }
 
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 7] = [
+        HashAlgorithm::Keccak256,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::DoubleSha256,
+        HashAlgorithm::Sha3_256,
+        HashAlgorithm::Blake2b256,
+        HashAlgorithm::Blake3,
+        HashAlgorithm::PoseidonTest,
+    ];
+
+    /// The algorithm whose `MerkleHasher::name` is `name`
+    pub fn from_name(name: &str) -> Option<HashAlgorithm> {
+        HashAlgorithm::ALL.into_iter().find(|algorithm| algorithm.name() == name)
+    }
 
-pub fn hash_words(words: Vec<&str>) -> Vec<String> {
+    /// The algorithm after this one in `ALL`, wrapping around
+    pub fn next(self) -> HashAlgorithm {
+        let position = HashAlgorithm::ALL.iter().position(|algorithm| *algorithm == self).unwrap_or(0);
+        HashAlgorithm::ALL[(position + 1) % HashAlgorithm::ALL.len()]
+    }
+}
+
+impl MerkleHasher for HashAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Keccak256 => Keccak256.name(),
+            HashAlgorithm::Sha256 => Sha256.name(),
+            HashAlgorithm::DoubleSha256 => DoubleSha256.name(),
+            HashAlgorithm::Sha3_256 => Sha3_256.name(),
+            HashAlgorithm::Blake2b256 => Blake2b256.name(),
+            HashAlgorithm::Blake3 => Blake3.name(),
+            HashAlgorithm::PoseidonTest => PoseidonTest.name(),
+        }
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        match self {
+            HashAlgorithm::Keccak256 => Keccak256.hash(data),
+            HashAlgorithm::Sha256 => Sha256.hash(data),
+            HashAlgorithm::DoubleSha256 => DoubleSha256.hash(data),
+            HashAlgorithm::Sha3_256 => Sha3_256.hash(data),
+            HashAlgorithm::Blake2b256 => Blake2b256.hash(data),
+            HashAlgorithm::Blake3 => Blake3.hash(data),
+            HashAlgorithm::PoseidonTest => PoseidonTest.hash(data),
+        }
+    }
+
+    fn hash_reader(&self, reader: &mut dyn Read) -> io::Result<Digest> {
+        match self {
+            HashAlgorithm::Keccak256 => Keccak256.hash_reader(reader),
+            HashAlgorithm::Sha256 => Sha256.hash_reader(reader),
+            HashAlgorithm::DoubleSha256 => DoubleSha256.hash_reader(reader),
+            HashAlgorithm::Sha3_256 => Sha3_256.hash_reader(reader),
+            HashAlgorithm::Blake2b256 => Blake2b256.hash_reader(reader),
+            HashAlgorithm::Blake3 => Blake3.hash_reader(reader),
+            HashAlgorithm::PoseidonTest => PoseidonTest.hash_reader(reader),
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+}
+
+pub fn keccak_256(data: impl AsRef<[u8]>) -> Digest {
+    Keccak256.hash(data.as_ref())
+}
+
+/// Hash a leaf's data, behind `LEAF_PREFIX` when domain separation is on
+pub fn hash_leaf<H: MerkleHasher>(hasher: &H, data: &[u8], options: &TreeOptions) -> Digest {
+    if options.domain_separation {
+        let mut prefixed = Vec::with_capacity(data.len() + 1);
+        prefixed.push(LEAF_PREFIX);
+        prefixed.extend_from_slice(data);
+        return hasher.hash(&prefixed);
+    }
+    hasher.hash(data)
+}
+
+pub fn hash_word<H: MerkleHasher>(hasher: &H, word: &str, options: &TreeOptions) -> Digest {
+    hash_leaf(hasher, word.as_bytes(), options)
+}
+
+pub fn hash_words<H: MerkleHasher>(hasher: &H, words: Vec<&str>, options: &TreeOptions) -> Vec<Digest> {
     let mut hashes = Vec::new();
     for word in words {
-        let hash = keccak_256(word);
+        let hash = hash_word(hasher, word, options);
         hashes.push(hash);
     }
-    return hashes;
+    hashes
 }
 
-pub fn hash_combination(left: &String, right: &String) -> String {
-    let formatted = &format!("{}{}", left, right);
-    return keccak_256(formatted);
-} 
+/// Hash two children into their parent, behind `NODE_PREFIX` when domain separation is on.
+/// With `TreeOptions::sorted_pairs` the smaller child goes first whichever side it is on.
+pub fn hash_combination<H: MerkleHasher>(hasher: &H, left: &Digest, right: &Digest, options: &TreeOptions) -> Digest {
+    let (left, right) = if options.sorted_pairs && right < left { (right, left) } else { (left, right) };
+    let mut combined = Vec::with_capacity(129);
+    if options.domain_separation {
+        combined.push(NODE_PREFIX);
+    }
+
+    match options.encoding {
+        PairEncoding::Bytes => {
+            combined.extend_from_slice(left.as_bytes());
+            combined.extend_from_slice(right.as_bytes());
+        }
+        PairEncoding::LegacyHex => {
+            combined.extend_from_slice(format!("{}{}", left.to_hex(), right.to_hex()).as_bytes());
+        }
+    }
+    hasher.hash(&combined)
+}
@@ -0,0 +1,109 @@
+//! A Poseidon-style sponge over the Goldilocks field (p = 2^64 - 2^32 + 1).
+//!
+//! It has the shape of Poseidon — x^7 S-box, full and partial rounds, a circulant
+//! mixing layer — so trees can be exercised with an arithmetic hash like the ones
+//! used by zk-friendly chains. The round constants and matrix are generated here
+//! rather than taken from the reference parameters, so it is only fit for tests.
+
+use super::{Digest, MerkleHasher};
+
+const P: u64 = 0xffff_ffff_0000_0001;
+const WIDTH: usize = 8;
+const RATE: usize = 4;
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 22;
+const MDS_ROW: [u64; WIDTH] = [7, 1, 3, 8, 8, 3, 4, 9];
+
+/// Bytes packed into one field element, small enough to always be below p
+const BYTES_PER_ELEMENT: usize = 7;
+
+#[derive(Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PoseidonTest;
+
+impl MerkleHasher for PoseidonTest {
+    fn name(&self) -> &'static str {
+        "Poseidon (test)"
+    }
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        let constants = round_constants();
+        let mut state = [0u64; WIDTH];
+        // the capacity starts with the input length so inputs differing only in padding can't collide
+        state[RATE] = data.len() as u64 % P;
+
+        let mut padded = data.to_vec();
+        padded.push(1);
+        let block_bytes = BYTES_PER_ELEMENT * RATE;
+        padded.resize(padded.len().div_ceil(block_bytes) * block_bytes, 0);
+
+        for block in padded.chunks(block_bytes) {
+            for (i, chunk) in block.chunks(BYTES_PER_ELEMENT).enumerate() {
+                let mut bytes = [0u8; 8];
+                bytes[..chunk.len()].copy_from_slice(chunk);
+                state[i] = add(state[i], u64::from_le_bytes(bytes));
+            }
+            permute(&mut state, &constants);
+        }
+
+        let mut result = [0u8; 32];
+        for (i, element) in state[..4].iter().enumerate() {
+            result[i * 8..(i + 1) * 8].copy_from_slice(&element.to_le_bytes());
+        }
+        Digest(result)
+    }
+}
+
+fn add(a: u64, b: u64) -> u64 {
+    ((a as u128 + b as u128) % P as u128) as u64
+}
+
+fn mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % P as u128) as u64
+}
+
+fn sbox(x: u64) -> u64 {
+    let x2 = mul(x, x);
+    let x4 = mul(x2, x2);
+    mul(x4, mul(x2, x))
+}
+
+/// Deterministic round constants from a splitmix64 stream
+fn round_constants() -> Vec<u64> {
+    let mut seed: u64 = 0x506f_7365_6964_6f6e; // "Poseidon"
+    (0..(FULL_ROUNDS + PARTIAL_ROUNDS) * WIDTH)
+        .map(|_| {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            (z ^ (z >> 31)) % P
+        })
+        .collect()
+}
+
+fn permute(state: &mut [u64; WIDTH], constants: &[u64]) {
+    let half_full = FULL_ROUNDS / 2;
+
+    for round in 0..FULL_ROUNDS + PARTIAL_ROUNDS {
+        for (i, element) in state.iter_mut().enumerate() {
+            *element = add(*element, constants[round * WIDTH + i]);
+        }
+
+        if round < half_full || round >= half_full + PARTIAL_ROUNDS {
+            for element in state.iter_mut() {
+                *element = sbox(*element);
+            }
+        } else {
+            state[0] = sbox(state[0]);
+        }
+
+        let mut mixed = [0u64; WIDTH];
+        for (i, out) in mixed.iter_mut().enumerate() {
+            for (j, element) in state.iter().enumerate() {
+                *out = add(*out, mul(MDS_ROW[(j + WIDTH - i) % WIDTH], *element));
+            }
+        }
+        *state = mixed;
+    }
+}
@@ -0,0 +1,32 @@
+//! Data a tree can commit to.
+
+use std::borrow::Cow;
+
+use crate::hasher::to_hex;
+
+/// A leaf's canonical encoding, the bytes it is hashed from, and a label to draw it with.
+/// Anything that is `AsRef<[u8]>` is a leaf: `&str`, `String`, `Vec<u8>`, `[u8; N]` or a `Digest`.
+/// Other types implement `encode` with an encoding that doesn't vary between platforms or runs,
+/// such as fixed-width big-endian fields one after another.
+pub trait Leaf {
+    fn encode(&self) -> Cow<'_, [u8]>;
+
+    /// Human readable name, only used to draw the leaf in the GUI
+    fn label(&self) -> String {
+        label(&self.encode())
+    }
+}
+
+impl<T: AsRef<[u8]> + ?Sized> Leaf for T {
+    fn encode(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_ref())
+    }
+}
+
+/// `bytes` as text when they are printable UTF-8, as `0x` hex otherwise
+pub fn label(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ => format!("0x{}", to_hex(bytes)),
+    }
+}
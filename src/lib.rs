@@ -0,0 +1,18 @@
+//! Binary Merkle tree logic, usable without a renderer.
+//!
+//! The binary in `main.rs` is its command line, and opens the Bevy visualiser in
+//! `gui/mod.rs` when run without arguments. The visualiser is only built with the
+//! `gui` feature.
+
+pub mod bitcoin;
+pub mod chunked;
+pub mod codec;
+pub mod directory;
+pub mod error;
+pub mod hasher;
+pub mod leaf;
+pub mod mpt;
+pub mod openzeppelin;
+pub mod proof;
+pub mod sparse;
+pub mod tree;
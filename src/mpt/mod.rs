@@ -0,0 +1,432 @@
+//! Ethereum's Merkle Patricia Trie, as used for state, storage, transaction and receipt roots.
+//!
+//! Unlike the binary `tree::MerkleTree`, each node has up to 16 children, one per nibble of the key,
+//! and nodes are RLP encoded before hashing with `hasher::keccak_256`. Nodes shorter than 32 bytes
+//! are embedded in their parent instead of being referenced by hash.
+
+pub mod rlp;
+
+use std::collections::BTreeMap;
+
+use crate::hasher::{self, Digest};
+use crate::proof::ProofError;
+
+use self::rlp::Item;
+
+/// Root of a trie with no entries, `keccak_256(rlp(""))`
+pub const EMPTY_ROOT: Digest = Digest([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+/// Code hash of an account without code, `keccak_256("")`
+pub const EMPTY_CODE_HASH: Digest = Digest([
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+]);
+
+/// An account as stored in the state trie
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: u128,
+    pub storage_root: Digest,
+    pub code_hash: Digest,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            nonce: 0,
+            balance: 0,
+            storage_root: EMPTY_ROOT,
+            code_hash: EMPTY_CODE_HASH,
+        }
+    }
+}
+
+impl Account {
+    /// `rlp([nonce, balance, storage_root, code_hash])`, the value kept in the state trie
+    pub fn rlp(&self) -> Vec<u8> {
+        rlp::encode_list(&[
+            rlp::encode_uint(self.nonce as u128),
+            rlp::encode_uint(self.balance),
+            rlp::encode_bytes(self.storage_root.as_bytes()),
+            rlp::encode_bytes(self.code_hash.as_bytes()),
+        ])
+    }
+}
+
+/// A trie over byte keys. A secure trie, like the state and storage tries, keys its entries by
+/// `keccak_256` of the key instead of the key itself.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct PatriciaTrie {
+    /// Values by trie key, after hashing for a secure trie
+    pub entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub secure: bool,
+}
+
+impl PatriciaTrie {
+    pub fn new() -> PatriciaTrie {
+        PatriciaTrie::default()
+    }
+
+    /// A trie that hashes its keys, as the state and storage tries do
+    pub fn secure() -> PatriciaTrie {
+        PatriciaTrie { secure: true, ..Default::default() }
+    }
+
+    /// The path `key` is stored under
+    pub fn trie_key(&self, key: &[u8]) -> Vec<u8> {
+        if self.secure {
+            hasher::keccak_256(key).as_bytes().to_vec()
+        } else {
+            key.to_vec()
+        }
+    }
+
+    /// Set `key` to `value`. An empty value removes the key, as in Ethereum.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let trie_key = self.trie_key(key);
+        if value.is_empty() {
+            self.entries.remove(&trie_key);
+        } else {
+            self.entries.insert(trie_key, value.to_vec());
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries.get(&self.trie_key(key)).map(|value| value.as_slice())
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let trie_key = self.trie_key(key);
+        self.entries.remove(&trie_key)
+    }
+
+    pub fn root(&self) -> Digest {
+        hasher::keccak_256(self.root_node().encode())
+    }
+
+    /// The RLP encoded nodes from the root down to `key`, as `eth_getProof` returns them.
+    /// Nodes embedded in their parent are not listed. For a missing key the nodes show where the
+    /// path ends, which proves the absence.
+    pub fn prove(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        let path = nibbles(&self.trie_key(key));
+        let mut proof = vec![];
+        let mut node = self.root_node();
+        let mut depth = 0;
+        let mut is_root = true;
+
+        loop {
+            let encoded = node.encode();
+            if is_root || encoded.len() >= 32 {
+                proof.push(encoded);
+            }
+            is_root = false;
+
+            node = match node {
+                Node::Extension { path: extension, child } if path[depth..].starts_with(&extension) => {
+                    depth += extension.len();
+                    *child
+                }
+                Node::Branch { mut children, .. } if depth < path.len() => {
+                    let child = std::mem::replace(&mut children[path[depth] as usize], Node::Empty);
+                    depth += 1;
+                    child
+                }
+                _ => break,
+            };
+            if node == Node::Empty {
+                break;
+            }
+        }
+        proof
+    }
+
+    fn root_node(&self) -> Node {
+        let entries: Vec<(Vec<u8>, &[u8])> = self.entries.iter()
+            .map(|(key, value)| (nibbles(key), value.as_slice()))
+            .collect();
+        Node::build(&entries, 0)
+    }
+}
+
+/// Root of a trie keyed by `rlp(index)`, as the transaction and receipt roots of a block are.
+/// `items` are the RLP (or typed envelope) encodings of the transactions or receipts.
+pub fn ordered_trie_root(items: &[Vec<u8>]) -> Digest {
+    let mut trie = PatriciaTrie::new();
+    for (index, item) in items.iter().enumerate() {
+        trie.insert(&rlp::encode_uint(index as u128), item);
+    }
+    trie.root()
+}
+
+/// Follow `proof` from `root` down the path `key` and return the value found there,
+/// `None` if the proof shows the key is absent.
+///
+/// `key` is the trie key: `keccak_256` of the address or storage slot for a secure trie.
+/// Fails when a node doesn't hash to the reference its parent holds, so a proof for
+/// another root never gives a value.
+pub fn verify_proof(root: &Digest, key: &[u8], proof: &[Vec<u8>]) -> Result<Option<Vec<u8>>, ProofError> {
+    let path = nibbles(key);
+    let mut nodes = proof.iter();
+    let mut depth = 0;
+
+    let first = nodes.next().ok_or(ProofError::MalformedProof)?;
+    if hasher::keccak_256(first) != *root {
+        return Err(ProofError::MalformedProof);
+    }
+    let mut item = rlp::decode(first).map_err(|_| ProofError::MalformedProof)?;
+
+    let value = loop {
+        let reference = match item {
+            Item::List(mut fields) if fields.len() == 17 => {
+                if depth == path.len() {
+                    break bytes(&fields[16])?.to_vec();
+                }
+                let reference = fields.swap_remove(path[depth] as usize);
+                depth += 1;
+                reference
+            }
+            Item::List(mut fields) if fields.len() == 2 => {
+                let (node_path, is_leaf) = decode_hex_prefix(bytes(&fields[0])?)?;
+                if is_leaf {
+                    if path[depth..] == node_path[..] {
+                        break bytes(&fields[1])?.to_vec();
+                    }
+                    break Vec::new();
+                }
+                if !path[depth..].starts_with(&node_path) {
+                    break Vec::new();
+                }
+                depth += node_path.len();
+                fields.swap_remove(1)
+            }
+            // the root of an empty trie
+            Item::Bytes(empty) if empty.is_empty() => break Vec::new(),
+            _ => return Err(ProofError::MalformedProof),
+        };
+
+        item = match reference {
+            // an empty slot ends the path
+            Item::Bytes(hash) if hash.is_empty() => break Vec::new(),
+            Item::Bytes(hash) if hash.len() == 32 => {
+                let node = nodes.next().ok_or(ProofError::MalformedProof)?;
+                if hasher::keccak_256(node).as_bytes()[..] != hash[..] {
+                    return Err(ProofError::MalformedProof);
+                }
+                rlp::decode(node).map_err(|_| ProofError::MalformedProof)?
+            }
+            // nodes under 32 bytes sit inside their parent
+            embedded @ Item::List(_) => embedded,
+            Item::Bytes(_) => return Err(ProofError::MalformedProof),
+        };
+    };
+
+    if nodes.next().is_some() {
+        return Err(ProofError::MalformedProof);
+    }
+    Ok(if value.is_empty() { None } else { Some(value) })
+}
+
+/// Check that `proof` shows `key` holding `value` under `root`, or holding nothing for `None`
+pub fn verify(root: &Digest, key: &[u8], value: Option<&[u8]>, proof: &[Vec<u8>]) -> Result<bool, ProofError> {
+    Ok(verify_proof(root, key, proof)?.as_deref() == value)
+}
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Default)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf { path: Vec<u8>, value: Vec<u8> },
+    Extension { path: Vec<u8>, child: Box<Node> },
+    Branch { children: Box<[Node; 16]>, value: Vec<u8> },
+}
+
+impl Node {
+    /// The node holding `entries`, whose nibble paths are sorted, unique and agree up to `depth`
+    fn build(entries: &[(Vec<u8>, &[u8])], depth: usize) -> Node {
+        match entries {
+            [] => return Node::Empty,
+            [(path, value)] => return Node::Leaf { path: path[depth..].to_vec(), value: value.to_vec() },
+            _ => {}
+        }
+
+        // sorted, so the first and last path share the prefix every path shares
+        let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+        let common = first[depth..].iter().zip(&last[depth..]).take_while(|(a, b)| a == b).count();
+        if common > 0 {
+            return Node::Extension {
+                path: first[depth..depth + common].to_vec(),
+                child: Box::new(Node::build(entries, depth + common)),
+            };
+        }
+
+        let mut children: [Node; 16] = Default::default();
+        let mut value = Vec::new();
+        let mut rest = entries;
+        if rest[0].0.len() == depth {
+            value = rest[0].1.to_vec();
+            rest = &rest[1..];
+        }
+        for (nibble, child) in children.iter_mut().enumerate() {
+            let count = rest.iter().take_while(|(path, _)| path[depth] as usize == nibble).count();
+            *child = Node::build(&rest[..count], depth + 1);
+            rest = &rest[count..];
+        }
+        Node::Branch { children: Box::new(children), value }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Node::Empty => rlp::encode_bytes(&[]),
+            Node::Leaf { path, value } => rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, true)),
+                rlp::encode_bytes(value),
+            ]),
+            Node::Extension { path, child } => rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, false)),
+                child.reference(),
+            ]),
+            Node::Branch { children, value } => {
+                let mut fields: Vec<Vec<u8>> = children.iter().map(Node::reference).collect();
+                fields.push(rlp::encode_bytes(value));
+                rlp::encode_list(&fields)
+            }
+        }
+    }
+
+    /// How a parent refers to this node: embedded when its encoding is under 32 bytes, else by hash
+    fn reference(&self) -> Vec<u8> {
+        let encoded = self.encode();
+        if encoded.len() < 32 {
+            encoded
+        } else {
+            rlp::encode_bytes(hasher::keccak_256(&encoded).as_bytes())
+        }
+    }
+}
+
+fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// Pack a nibble path with the flags for leaf and odd length in the first nibble
+fn hex_prefix(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 2 } else { 0 };
+    let mut packed = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        packed.push(((flag + 1) << 4) | path[0]);
+        &path[1..]
+    } else {
+        packed.push(flag << 4);
+        path
+    };
+    packed.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    packed
+}
+
+fn decode_hex_prefix(packed: &[u8]) -> Result<(Vec<u8>, bool), ProofError> {
+    let first = *packed.first().ok_or(ProofError::MalformedProof)?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(ProofError::MalformedProof);
+    }
+    let mut path = Vec::new();
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(nibbles(&packed[1..]));
+    Ok((path, flag & 2 == 2))
+}
+
+fn bytes(item: &Item) -> Result<&[u8], ProofError> {
+    match item {
+        Item::Bytes(bytes) => Ok(bytes),
+        Item::List(_) => Err(ProofError::MalformedProof),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trie(entries: &[(&str, &str)]) -> PatriciaTrie {
+        let mut trie = PatriciaTrie::new();
+        for (key, value) in entries {
+            trie.insert(key.as_bytes(), value.as_bytes());
+        }
+        trie
+    }
+
+    fn root(hex: &str) -> Digest {
+        Digest::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn empty_trie_has_the_empty_root() {
+        assert_eq!(PatriciaTrie::new().root(), EMPTY_ROOT);
+        assert_eq!(EMPTY_ROOT, root("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"));
+        assert_eq!(hasher::keccak_256(rlp::encode_bytes(&[])), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn matches_the_ethereum_test_roots() {
+        let dogs = trie(&[("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")]);
+        assert_eq!(dogs.root(), root("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"));
+
+        let puppy = trie(&[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")]);
+        assert_eq!(puppy.root(), root("5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"));
+
+        let foo = trie(&[("foo", "bar"), ("food", "bass")]);
+        assert_eq!(foo.root(), root("17beaa1648bafa633cda809c90c04af50fc8aed3cb40d16efbddee6fdf63c4c3"));
+    }
+
+    #[test]
+    fn root_does_not_depend_on_insertion_order() {
+        let forward = trie(&[("do", "verb"), ("dog", "puppy"), ("doge", "coin"), ("horse", "stallion")]);
+        let backward = trie(&[("horse", "stallion"), ("doge", "coin"), ("dog", "puppy"), ("do", "verb")]);
+        assert_eq!(forward.root(), backward.root());
+    }
+
+    #[test]
+    fn empty_values_remove_keys() {
+        let mut puppy = trie(&[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")]);
+        puppy.insert(b"horse", b"");
+        assert_eq!(puppy.root(), trie(&[("do", "verb"), ("doge", "coin"), ("dog", "puppy")]).root());
+    }
+
+    #[test]
+    fn proofs_verify_present_and_absent_keys() {
+        let puppy = trie(&[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")]);
+        let root = puppy.root();
+        for (key, value) in [("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")] {
+            let proof = puppy.prove(key.as_bytes());
+            assert_eq!(verify_proof(&root, key.as_bytes(), &proof), Ok(Some(value.as_bytes().to_vec())));
+            assert_eq!(verify(&root, key.as_bytes(), Some(b"other"), &proof), Ok(false));
+        }
+        for key in ["d", "dogs", "cat", "horses"] {
+            let proof = puppy.prove(key.as_bytes());
+            assert_eq!(verify_proof(&root, key.as_bytes(), &proof), Ok(None));
+        }
+
+        let proof = puppy.prove(b"dog");
+        assert_eq!(verify_proof(&EMPTY_ROOT, b"dog", &proof), Err(ProofError::MalformedProof));
+    }
+
+    #[test]
+    fn secure_trie_hashes_its_keys() {
+        let mut secure = PatriciaTrie::secure();
+        secure.insert(b"dog", b"puppy");
+        let root = secure.root();
+        let proof = secure.prove(b"dog");
+        assert_eq!(verify_proof(&root, hasher::keccak_256(b"dog").as_bytes(), &proof), Ok(Some(b"puppy".to_vec())));
+        assert_ne!(root, trie(&[("dog", "puppy")]).root());
+    }
+}
@@ -0,0 +1,201 @@
+//! Recursive Length Prefix encoding, as used by Ethereum for trie nodes, accounts and receipts.
+
+use std::fmt;
+
+/// A decoded RLP item: a byte string or a list of items
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Item {
+    Bytes(Vec<u8>),
+    List(Vec<Item>),
+}
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum RlpError {
+    /// The input ends before the item it announces
+    UnexpectedEnd,
+    /// Bytes are left over after the item
+    TrailingBytes,
+    /// A length or single byte is not written in its shortest form
+    NonCanonical,
+}
+
+impl fmt::Display for RlpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RlpError::UnexpectedEnd => write!(f, "rlp input ends early"),
+            RlpError::TrailingBytes => write!(f, "rlp input has trailing bytes"),
+            RlpError::NonCanonical => write!(f, "rlp item is not canonically encoded"),
+        }
+    }
+}
+
+impl std::error::Error for RlpError {}
+
+pub fn encode(item: &Item) -> Vec<u8> {
+    match item {
+        Item::Bytes(bytes) => encode_bytes(bytes),
+        Item::List(items) => encode_list(&items.iter().map(encode).collect::<Vec<_>>()),
+    }
+}
+
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut encoded = length_prefix(bytes.len(), 0x80);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// Encode a list from items that are already RLP encoded
+pub fn encode_list(encoded_items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = encoded_items.concat();
+    let mut encoded = length_prefix(payload.len(), 0xc0);
+    encoded.extend_from_slice(&payload);
+    encoded
+}
+
+/// An unsigned integer as a big-endian byte string without leading zeros, zero being empty
+pub fn encode_uint(value: u128) -> Vec<u8> {
+    encode_bytes(&trimmed_be(value))
+}
+
+/// Decode exactly one item from `data`
+pub fn decode(data: &[u8]) -> Result<Item, RlpError> {
+    let (item, rest) = decode_item(data)?;
+    if !rest.is_empty() {
+        return Err(RlpError::TrailingBytes);
+    }
+    Ok(item)
+}
+
+fn decode_item(data: &[u8]) -> Result<(Item, &[u8]), RlpError> {
+    let first = *data.first().ok_or(RlpError::UnexpectedEnd)?;
+    match first {
+        0x00..=0x7f => Ok((Item::Bytes(vec![first]), &data[1..])),
+        0x80..=0xbf => {
+            let (payload, rest) = payload(data, 0x80)?;
+            if payload.len() == 1 && payload[0] < 0x80 {
+                return Err(RlpError::NonCanonical);
+            }
+            Ok((Item::Bytes(payload.to_vec()), rest))
+        }
+        0xc0..=0xff => {
+            let (mut payload, rest) = payload(data, 0xc0)?;
+            let mut items = Vec::new();
+            while !payload.is_empty() {
+                let (item, remaining) = decode_item(payload)?;
+                items.push(item);
+                payload = remaining;
+            }
+            Ok((Item::List(items), rest))
+        }
+    }
+}
+
+/// Split the payload announced by the prefix at the start of `data` from what follows it
+fn payload(data: &[u8], offset: u8) -> Result<(&[u8], &[u8]), RlpError> {
+    let prefix = data[0] - offset;
+    let (start, length) = if prefix <= 55 {
+        (1, prefix as usize)
+    } else {
+        let length_bytes = (prefix - 55) as usize;
+        let bytes = data.get(1..1 + length_bytes).ok_or(RlpError::UnexpectedEnd)?;
+        if bytes[0] == 0 || length_bytes > std::mem::size_of::<usize>() {
+            return Err(RlpError::NonCanonical);
+        }
+        let length = bytes.iter().fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        if length <= 55 {
+            return Err(RlpError::NonCanonical);
+        }
+        (1 + length_bytes, length)
+    };
+    let end = start.checked_add(length).ok_or(RlpError::UnexpectedEnd)?;
+    let payload = data.get(start..end).ok_or(RlpError::UnexpectedEnd)?;
+    Ok((payload, &data[end..]))
+}
+
+fn length_prefix(length: usize, offset: u8) -> Vec<u8> {
+    if length <= 55 {
+        return vec![offset + length as u8];
+    }
+    let length_bytes = trimmed_be(length as u128);
+    let mut prefix = vec![offset + 55 + length_bytes.len() as u8];
+    prefix.extend_from_slice(&length_bytes);
+    prefix
+}
+
+fn trimmed_be(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let first = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    bytes[first..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(text: &str) -> Item {
+        Item::Bytes(text.as_bytes().to_vec())
+    }
+
+    fn list(items: Vec<Item>) -> Item {
+        Item::List(items)
+    }
+
+    fn round_trip(item: Item, encoded: &[u8]) {
+        assert_eq!(encode(&item), encoded);
+        assert_eq!(decode(encoded), Ok(item));
+    }
+
+    #[test]
+    fn encodes_the_spec_examples() {
+        round_trip(bytes("dog"), &[0x83, b'd', b'o', b'g']);
+        round_trip(list(vec![bytes("cat"), bytes("dog")]), &[0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']);
+        round_trip(bytes(""), &[0x80]);
+        round_trip(list(vec![]), &[0xc0]);
+        round_trip(Item::Bytes(vec![0x00]), &[0x00]);
+        round_trip(Item::Bytes(vec![0x0f]), &[0x0f]);
+        round_trip(Item::Bytes(vec![0x04, 0x00]), &[0x82, 0x04, 0x00]);
+        // the set theoretical representation of three
+        round_trip(
+            list(vec![list(vec![]), list(vec![list(vec![])]), list(vec![list(vec![]), list(vec![list(vec![])])])]),
+            &[0xc7, 0xc0, 0xc1, 0xc0, 0xc3, 0xc0, 0xc1, 0xc0],
+        );
+    }
+
+    #[test]
+    fn encodes_long_strings_and_lists() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let mut encoded = vec![0xb8, 0x38];
+        encoded.extend_from_slice(text.as_bytes());
+        round_trip(bytes(text), &encoded);
+
+        let items = vec![bytes(text); 5];
+        let encoded = encode(&list(items.clone()));
+        assert_eq!(&encoded[..3], &[0xf9, 0x01, 0x22]);
+        assert_eq!(decode(&encoded), Ok(list(items)));
+    }
+
+    #[test]
+    fn encodes_integers_without_leading_zeros() {
+        assert_eq!(encode_uint(0), [0x80]);
+        assert_eq!(encode_uint(15), [0x0f]);
+        assert_eq!(encode_uint(1024), [0x82, 0x04, 0x00]);
+        assert_eq!(encode_uint(u128::MAX)[0], 0x90);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(decode(&[]), Err(RlpError::UnexpectedEnd));
+        assert_eq!(decode(&[0x83, b'd', b'o']), Err(RlpError::UnexpectedEnd));
+        assert_eq!(decode(&[0x83, b'd', b'o', b'g', 0x00]), Err(RlpError::TrailingBytes));
+        // a single byte below 0x80 is its own encoding
+        assert_eq!(decode(&[0x81, 0x05]), Err(RlpError::NonCanonical));
+        // a long form length that fits the short form
+        assert_eq!(decode(&[0xb8, 0x01, 0xff]), Err(RlpError::NonCanonical));
+        assert_eq!(decode(&[0xb9, 0x00, 0x38]), Err(RlpError::NonCanonical));
+    }
+}
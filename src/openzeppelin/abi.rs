@@ -0,0 +1,310 @@
+//! Solidity ABI encoding of the static types and `bytes`/`string`, as `abi.encode` lays them out.
+//! Arrays and tuples are not supported.
+
+use std::fmt;
+
+use crate::hasher::{self, to_hex};
+
+/// A Solidity type a leaf value can have
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum AbiType {
+    Address,
+    Bool,
+    /// `uint<bits>`
+    Uint(u16),
+    /// `int<bits>`
+    Int(u16),
+    /// `bytes<length>`
+    FixedBytes(u8),
+    Bytes,
+    String,
+}
+
+/// A value of an `AbiType`. Integers are 32-byte big-endian words, negative ones in two's complement.
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum AbiValue {
+    Address([u8; 20]),
+    Bool(bool),
+    Uint([u8; 32]),
+    Int([u8; 32]),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum AbiError {
+    /// Not a type this module can encode
+    UnknownType(String),
+    /// The value can't be read as, or doesn't fit, the type
+    InvalidValue { ty: String, value: String },
+    /// A leaf has a different number of values than the leaf encoding has types
+    WrongArity { expected: usize, found: usize },
+}
+
+impl fmt::Display for AbiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiError::UnknownType(ty) => write!(f, "unsupported abi type {}", ty),
+            AbiError::InvalidValue { ty, value } => write!(f, "{} is not a valid {}", value, ty),
+            AbiError::WrongArity { expected, found } => write!(f, "expected {} values, found {}", expected, found),
+        }
+    }
+}
+
+impl std::error::Error for AbiError {}
+
+impl AbiType {
+    /// Parse a Solidity type name such as `address`, `uint256` or `bytes32`. `uint` and `int` are `256` bits.
+    pub fn parse(name: &str) -> Result<AbiType, AbiError> {
+        let unknown = || AbiError::UnknownType(name.to_string());
+        let size = |digits: &str, default: u16| -> Option<u16> {
+            if digits.is_empty() {
+                return Some(default);
+            }
+            // sizes are written without leading zeros
+            if digits.starts_with('0') {
+                return None;
+            }
+            digits.parse().ok()
+        };
+
+        match name {
+            "address" => Ok(AbiType::Address),
+            "bool" => Ok(AbiType::Bool),
+            "bytes" => Ok(AbiType::Bytes),
+            "string" => Ok(AbiType::String),
+            _ => {
+                if let Some(digits) = name.strip_prefix("uint") {
+                    let bits = size(digits, 256).filter(|bits| *bits % 8 == 0 && (8..=256).contains(bits)).ok_or_else(unknown)?;
+                    Ok(AbiType::Uint(bits))
+                } else if let Some(digits) = name.strip_prefix("int") {
+                    let bits = size(digits, 256).filter(|bits| *bits % 8 == 0 && (8..=256).contains(bits)).ok_or_else(unknown)?;
+                    Ok(AbiType::Int(bits))
+                } else if let Some(digits) = name.strip_prefix("bytes") {
+                    let length = size(digits, 0).filter(|length| (1..=32).contains(length)).ok_or_else(unknown)?;
+                    Ok(AbiType::FixedBytes(length as u8))
+                } else {
+                    Err(unknown())
+                }
+            }
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self, AbiType::Bytes | AbiType::String)
+    }
+}
+
+impl fmt::Display for AbiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiType::Address => write!(f, "address"),
+            AbiType::Bool => write!(f, "bool"),
+            AbiType::Uint(bits) => write!(f, "uint{}", bits),
+            AbiType::Int(bits) => write!(f, "int{}", bits),
+            AbiType::FixedBytes(length) => write!(f, "bytes{}", length),
+            AbiType::Bytes => write!(f, "bytes"),
+            AbiType::String => write!(f, "string"),
+        }
+    }
+}
+
+impl AbiValue {
+    /// Read a value of type `ty` from text as the JS tooling writes it: `0x` hex for addresses and bytes,
+    /// decimal or `0x` hex for integers, `true`/`false` for booleans
+    pub fn parse(ty: &AbiType, text: &str) -> Result<AbiValue, AbiError> {
+        let invalid = || AbiError::InvalidValue { ty: ty.to_string(), value: text.to_string() };
+
+        let value = match ty {
+            AbiType::Address => {
+                let bytes = parse_hex(text).filter(|bytes| bytes.len() == 20).ok_or_else(invalid)?;
+                let mut address = [0u8; 20];
+                address.copy_from_slice(&bytes);
+                AbiValue::Address(address)
+            }
+            AbiType::Bool => match text {
+                "true" => AbiValue::Bool(true),
+                "false" => AbiValue::Bool(false),
+                _ => return Err(invalid()),
+            },
+            AbiType::Uint(_) => AbiValue::Uint(parse_uint(text).ok_or_else(invalid)?),
+            AbiType::Int(_) => {
+                let (negative, digits) = match text.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, text),
+                };
+                let magnitude = parse_uint(digits).ok_or_else(invalid)?;
+                let word = if negative { negate(magnitude) } else { magnitude };
+                // the magnitude has to leave the sign bit for the sign
+                let sign = word[0] & 0x80 != 0;
+                if magnitude != [0u8; 32] && sign != negative {
+                    return Err(invalid());
+                }
+                AbiValue::Int(word)
+            }
+            AbiType::FixedBytes(_) => AbiValue::FixedBytes(parse_hex(text).ok_or_else(invalid)?),
+            AbiType::Bytes => AbiValue::Bytes(parse_hex(text).ok_or_else(invalid)?),
+            AbiType::String => AbiValue::String(text.to_string()),
+        };
+
+        if !value.fits(ty) {
+            return Err(invalid());
+        }
+        Ok(value)
+    }
+
+    /// Whether the value is of type `ty` and within its range
+    pub fn fits(&self, ty: &AbiType) -> bool {
+        match (self, ty) {
+            (AbiValue::Address(_), AbiType::Address)
+            | (AbiValue::Bool(_), AbiType::Bool)
+            | (AbiValue::Bytes(_), AbiType::Bytes)
+            | (AbiValue::String(_), AbiType::String) => true,
+            (AbiValue::Uint(word), AbiType::Uint(bits)) => {
+                word[..32 - *bits as usize / 8].iter().all(|byte| *byte == 0)
+            }
+            (AbiValue::Int(word), AbiType::Int(bits)) => {
+                // sign extended from the type's width
+                let unused = 32 - *bits as usize / 8;
+                let fill = if word[unused.min(31)] & 0x80 != 0 { 0xff } else { 0 };
+                word[..unused].iter().all(|byte| *byte == fill)
+            }
+            (AbiValue::FixedBytes(bytes), AbiType::FixedBytes(length)) => bytes.len() == *length as usize,
+            _ => false,
+        }
+    }
+
+    /// The value as text `parse` reads back, addresses with the EIP-55 checksum
+    pub fn to_text(&self) -> String {
+        match self {
+            AbiValue::Address(address) => checksum_address(address),
+            AbiValue::Bool(value) => value.to_string(),
+            AbiValue::Uint(word) => to_decimal(*word),
+            AbiValue::Int(word) if word[0] & 0x80 != 0 => format!("-{}", to_decimal(negate(*word))),
+            AbiValue::Int(word) => to_decimal(*word),
+            AbiValue::FixedBytes(bytes) | AbiValue::Bytes(bytes) => format!("0x{}", to_hex(bytes)),
+            AbiValue::String(text) => text.clone(),
+        }
+    }
+
+    fn head(&self) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        match self {
+            AbiValue::Address(address) => word[12..].copy_from_slice(address),
+            AbiValue::Bool(value) => word[31] = *value as u8,
+            AbiValue::Uint(value) | AbiValue::Int(value) => word = *value,
+            AbiValue::FixedBytes(bytes) => word[..bytes.len()].copy_from_slice(bytes),
+            AbiValue::Bytes(_) | AbiValue::String(_) => {}
+        }
+        word
+    }
+}
+
+/// `abi.encode(values...)`: a 32-byte head per value, with `bytes` and `string` in a tail the head points to
+pub fn encode(values: &[AbiValue]) -> Vec<u8> {
+    let mut heads = Vec::with_capacity(values.len() * 32);
+    let mut tail = Vec::new();
+    for value in values {
+        let data = match value {
+            AbiValue::Bytes(bytes) => bytes.as_slice(),
+            AbiValue::String(text) => text.as_bytes(),
+            _ => {
+                heads.extend_from_slice(&value.head());
+                continue;
+            }
+        };
+
+        heads.extend_from_slice(&uint_word((values.len() * 32 + tail.len()) as u128));
+        tail.extend_from_slice(&uint_word(data.len() as u128));
+        tail.extend_from_slice(data);
+        tail.resize(tail.len().div_ceil(32) * 32, 0);
+    }
+    heads.extend_from_slice(&tail);
+    heads
+}
+
+fn uint_word(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let hex = text.strip_prefix("0x")?;
+    // `from_str_radix` alone would also take a sign, as in "+f"
+    if hex.len() % 2 != 0 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+/// A 256-bit unsigned integer from decimal or `0x` hex
+fn parse_uint(text: &str) -> Option<[u8; 32]> {
+    let (digits, radix) = match text.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (text, 10),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut word = [0u8; 32];
+    for digit in digits.chars() {
+        let mut carry = digit.to_digit(radix)?;
+        for byte in word.iter_mut().rev() {
+            let value = *byte as u32 * radix + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(word)
+}
+
+fn to_decimal(mut word: [u8; 32]) -> String {
+    let mut digits = Vec::new();
+    loop {
+        let mut remainder = 0u32;
+        for byte in word.iter_mut() {
+            let value = (remainder << 8) | *byte as u32;
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+        if word == [0u8; 32] {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Two's complement negation
+fn negate(word: [u8; 32]) -> [u8; 32] {
+    let mut negated = word.map(|byte| !byte);
+    for byte in negated.iter_mut().rev() {
+        let (sum, overflow) = byte.overflowing_add(1);
+        *byte = sum;
+        if !overflow {
+            break;
+        }
+    }
+    negated
+}
+
+/// `0x` hex with letters uppercased where the nibble of `keccak_256(lowercase hex)` is 8 or more (EIP-55)
+fn checksum_address(address: &[u8; 20]) -> String {
+    let hex = to_hex(address);
+    let hash = hasher::keccak_256(hex.as_bytes());
+    let checksummed: String = hex.chars().enumerate().map(|(i, c)| {
+        let nibble = (hash.0[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+    }).collect();
+    format!("0x{}", checksummed)
+}
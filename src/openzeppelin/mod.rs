@@ -0,0 +1,410 @@
+//! Trees compatible with OpenZeppelin's `StandardMerkleTree` (`@openzeppelin/merkle-tree`) and
+//! `MerkleProof.verify` in Solidity.
+//!
+//! A leaf is `keccak_256(keccak_256(abi.encode(values...)))` and a parent is the hash of its two
+//! children in ascending order, so proofs carry no sides. The tree is stored as a flat array with
+//! the root at 0 and the children of `i` at `2i + 1` and `2i + 2`, leaves last and in reverse,
+//! which is not the level layout of `tree::MerkleTree` once the leaf count isn't a power of two.
+
+pub mod abi;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hasher::{self, Digest};
+use crate::proof::ProofError;
+
+use self::abi::{AbiError, AbiType, AbiValue};
+
+/// `format` of the JSON dumps this module reads and writes
+pub const FORMAT: &str = "standard-v1";
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum StandardTreeError {
+    Abi(AbiError),
+    /// A tree needs at least one leaf
+    EmptyTree,
+    IndexOutOfRange { index: usize, size: usize },
+    /// A multiproof asks for the same leaf twice
+    DuplicateIndex(usize),
+    /// The dump is not JSON of the expected shape
+    Json(String),
+    UnsupportedFormat(String),
+    /// The dump's nodes or leaf hashes don't match its values
+    InvalidDump,
+}
+
+impl fmt::Display for StandardTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StandardTreeError::Abi(error) => write!(f, "{}", error),
+            StandardTreeError::EmptyTree => write!(f, "the tree has no leaves"),
+            StandardTreeError::IndexOutOfRange { index, size } => write!(f, "index {} is out of range for {} values", index, size),
+            StandardTreeError::DuplicateIndex(index) => write!(f, "value {} is asked for twice", index),
+            StandardTreeError::Json(error) => write!(f, "invalid dump: {}", error),
+            StandardTreeError::UnsupportedFormat(format) => write!(f, "unsupported dump format {}", format),
+            StandardTreeError::InvalidDump => write!(f, "the dump's tree doesn't match its values"),
+        }
+    }
+}
+
+impl std::error::Error for StandardTreeError {}
+
+impl From<AbiError> for StandardTreeError {
+    fn from(error: AbiError) -> Self {
+        StandardTreeError::Abi(error)
+    }
+}
+
+/// A leaf's values and where its hash sits in `StandardMerkleTree::tree`
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct StandardValue {
+    pub value: Vec<AbiValue>,
+    pub tree_index: usize,
+}
+
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct StandardMerkleTree {
+    /// Type of each value in a leaf
+    pub leaf_encoding: Vec<AbiType>,
+    /// Every node, the root first
+    pub tree: Vec<Digest>,
+    /// Leaves in the order they were given
+    pub values: Vec<StandardValue>,
+}
+
+/// Proof of several leaves at once, in the shape `MerkleProof.multiProofVerify` takes
+#[derive(Clone)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StandardMultiProof {
+    /// Indices into `StandardMerkleTree::values`, in the order of `leaves`
+    pub indices: Vec<usize>,
+    pub leaves: Vec<Digest>,
+    pub proof: Vec<Digest>,
+    pub proof_flags: Vec<bool>,
+}
+
+impl StandardMerkleTree {
+    /// Build a tree the way `StandardMerkleTree.of(values, leafEncoding)` does, sorting the leaves by hash
+    pub fn of(values: Vec<Vec<AbiValue>>, leaf_encoding: Vec<AbiType>) -> Result<StandardMerkleTree, StandardTreeError> {
+        if values.is_empty() {
+            return Err(StandardTreeError::EmptyTree);
+        }
+        for value in &values {
+            check_types(value, &leaf_encoding)?;
+        }
+
+        let mut hashed: Vec<(usize, Digest)> = values.iter().map(|value| leaf_hash(value)).enumerate().collect();
+        hashed.sort_by_key(|(_, hash)| *hash);
+        let tree = make_tree(&hashed.iter().map(|(_, hash)| *hash).collect::<Vec<_>>());
+
+        let mut values: Vec<StandardValue> = values.into_iter().map(|value| StandardValue { value, tree_index: 0 }).collect();
+        for (leaf, (value_index, _)) in hashed.iter().enumerate() {
+            values[*value_index].tree_index = tree.len() - 1 - leaf;
+        }
+        Ok(StandardMerkleTree { leaf_encoding, tree, values })
+    }
+
+    /// Build a tree from values written as text, such as `["0x1111…", "5000000000000000000"]` with
+    /// `["address", "uint256"]`
+    pub fn from_text(values: &[Vec<&str>], leaf_encoding: &[&str]) -> Result<StandardMerkleTree, StandardTreeError> {
+        let types = leaf_encoding.iter().map(|name| AbiType::parse(name)).collect::<Result<Vec<_>, _>>()?;
+        let values = values.iter().map(|value| parse_values(value, &types)).collect::<Result<Vec<_>, _>>()?;
+        StandardMerkleTree::of(values, types)
+    }
+
+    pub fn root(&self) -> Digest {
+        self.tree[0]
+    }
+
+    /// Hash of the leaf holding `values[index]`
+    pub fn leaf_hash(&self, index: usize) -> Result<Digest, StandardTreeError> {
+        Ok(self.tree[self.tree_index(index)?])
+    }
+
+    /// Siblings from the leaf of `values[index]` up to the root, as `getProof(index)` returns them
+    pub fn proof(&self, index: usize) -> Result<Vec<Digest>, StandardTreeError> {
+        let mut node = self.tree_index(index)?;
+        let mut proof = Vec::new();
+        while node > 0 {
+            proof.push(self.tree[sibling(node)]);
+            node = parent(node);
+        }
+        Ok(proof)
+    }
+
+    /// Proof of the leaves of `values[index]` for each index, as `getMultiProof(indices)` returns it
+    pub fn multi_proof(&self, indices: &[usize]) -> Result<StandardMultiProof, StandardTreeError> {
+        let mut nodes = indices.iter().map(|index| self.tree_index(*index)).collect::<Result<Vec<_>, _>>()?;
+        // deepest nodes first, the order the contract consumes leaves in
+        nodes.sort_unstable_by(|a, b| b.cmp(a));
+        if let Some(pair) = nodes.windows(2).find(|pair| pair[0] == pair[1]) {
+            let index = self.values.iter().position(|value| value.tree_index == pair[0]).unwrap_or_default();
+            return Err(StandardTreeError::DuplicateIndex(index));
+        }
+
+        let mut proof = StandardMultiProof {
+            indices: nodes.iter().map(|node| self.values.iter().position(|value| value.tree_index == *node).unwrap()).collect(),
+            leaves: nodes.iter().map(|node| self.tree[*node]).collect(),
+            ..Default::default()
+        };
+
+        let mut stack: std::collections::VecDeque<usize> = nodes.into();
+        while let Some(&node) = stack.front().filter(|node| **node > 0) {
+            stack.pop_front();
+            if stack.front() == Some(&sibling(node)) {
+                proof.proof_flags.push(true);
+                stack.pop_front();
+            } else {
+                proof.proof_flags.push(false);
+                proof.proof.push(self.tree[sibling(node)]);
+            }
+            stack.push_back(parent(node));
+        }
+
+        if indices.is_empty() {
+            proof.proof.push(self.root());
+        }
+        Ok(proof)
+    }
+
+    /// The tree as the JSON `dump()` writes, readable by `StandardMerkleTree.load` in JS
+    pub fn dump(&self) -> String {
+        let dump = Dump {
+            format: FORMAT.to_string(),
+            leaf_encoding: self.leaf_encoding.iter().map(|ty| ty.to_string()).collect(),
+            tree: self.tree.iter().map(|hash| format!("0x{}", hash.to_hex())).collect(),
+            values: self.values.iter().map(|value| DumpValue {
+                value: value.value.iter().map(|value| match value {
+                    AbiValue::Bool(value) => serde_json::Value::Bool(*value),
+                    value => serde_json::Value::String(value.to_text()),
+                }).collect(),
+                tree_index: value.tree_index,
+            }).collect(),
+        };
+        serde_json::to_string_pretty(&dump).expect("a dump always serializes")
+    }
+
+    /// Read a JSON dump from `dump()` or the JS library, checking that its nodes and leaf hashes add up
+    pub fn load(json: &str) -> Result<StandardMerkleTree, StandardTreeError> {
+        let dump: Dump = serde_json::from_str(json).map_err(|error| StandardTreeError::Json(error.to_string()))?;
+        if dump.format != FORMAT {
+            return Err(StandardTreeError::UnsupportedFormat(dump.format));
+        }
+
+        let leaf_encoding = dump.leaf_encoding.iter().map(|name| AbiType::parse(name)).collect::<Result<Vec<_>, _>>()?;
+        let tree = dump.tree.iter().map(|hash| Digest::from_hex(hash).ok_or(StandardTreeError::InvalidDump)).collect::<Result<Vec<_>, _>>()?;
+        if !is_valid_tree(&tree) {
+            return Err(StandardTreeError::InvalidDump);
+        }
+
+        let mut values = Vec::with_capacity(dump.values.len());
+        for value in dump.values {
+            let text: Vec<String> = value.value.iter().map(|value| match value {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            }).collect();
+            let value_list = parse_values(&text.iter().map(|text| text.as_str()).collect::<Vec<_>>(), &leaf_encoding)?;
+
+            let is_leaf = value.tree_index < tree.len() && left_child(value.tree_index) >= tree.len();
+            if !is_leaf || tree[value.tree_index] != leaf_hash(&value_list) {
+                return Err(StandardTreeError::InvalidDump);
+            }
+            values.push(StandardValue { value: value_list, tree_index: value.tree_index });
+        }
+        Ok(StandardMerkleTree { leaf_encoding, tree, values })
+    }
+
+    fn tree_index(&self, index: usize) -> Result<usize, StandardTreeError> {
+        self.values.get(index).map(|value| value.tree_index)
+            .ok_or(StandardTreeError::IndexOutOfRange { index, size: self.values.len() })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Dump {
+    format: String,
+    leaf_encoding: Vec<String>,
+    tree: Vec<String>,
+    values: Vec<DumpValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpValue {
+    value: Vec<serde_json::Value>,
+    tree_index: usize,
+}
+
+/// `keccak_256(keccak_256(abi.encode(values...)))`
+pub fn leaf_hash(values: &[AbiValue]) -> Digest {
+    hasher::keccak_256(hasher::keccak_256(abi::encode(values)))
+}
+
+/// Hash of two nodes in ascending order, as `MerkleProof` does
+pub fn hash_pair(a: &Digest, b: &Digest) -> Digest {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    let mut combined = [0u8; 64];
+    combined[..32].copy_from_slice(low.as_bytes());
+    combined[32..].copy_from_slice(high.as_bytes());
+    hasher::keccak_256(combined)
+}
+
+/// `MerkleProof.verify(proof, root, leaf)`
+pub fn verify(root: &Digest, leaf: &Digest, proof: &[Digest]) -> bool {
+    process_proof(leaf, proof) == *root
+}
+
+/// The root `proof` leads to from `leaf`
+pub fn process_proof(leaf: &Digest, proof: &[Digest]) -> Digest {
+    proof.iter().fold(*leaf, |hash, sibling| hash_pair(&hash, sibling))
+}
+
+/// `MerkleProof.multiProofVerify(proof, proofFlags, root, leaves)`
+pub fn verify_multi(root: &Digest, proof: &StandardMultiProof) -> Result<bool, ProofError> {
+    Ok(process_multi_proof(&proof.leaves, &proof.proof, &proof.proof_flags)? == *root)
+}
+
+/// The root a multiproof leads to, as `MerkleProof.processMultiProof` computes it. Each flag hashes
+/// the next pending node with either the next leaf or computed hash (`true`) or the next proof hash (`false`).
+pub fn process_multi_proof(leaves: &[Digest], proof: &[Digest], proof_flags: &[bool]) -> Result<Digest, ProofError> {
+    if leaves.len() + proof.len() != proof_flags.len() + 1 {
+        return Err(ProofError::MalformedProof);
+    }
+
+    let mut hashes = Vec::with_capacity(proof_flags.len());
+    let (mut leaf_pos, mut hash_pos, mut proof_pos) = (0, 0, 0);
+    let mut next = |hashes: &Vec<Digest>| -> Result<Digest, ProofError> {
+        if leaf_pos < leaves.len() {
+            leaf_pos += 1;
+            Ok(leaves[leaf_pos - 1])
+        } else {
+            hash_pos += 1;
+            hashes.get(hash_pos - 1).copied().ok_or(ProofError::MalformedProof)
+        }
+    };
+
+    for flag in proof_flags {
+        let a = next(&hashes)?;
+        let b = if *flag {
+            next(&hashes)?
+        } else {
+            proof_pos += 1;
+            *proof.get(proof_pos - 1).ok_or(ProofError::MalformedProof)?
+        };
+        hashes.push(hash_pair(&a, &b));
+    }
+
+    match hashes.last() {
+        Some(root) if proof_pos == proof.len() => Ok(*root),
+        Some(_) => Err(ProofError::MalformedProof),
+        None => leaves.first().or(proof.first()).copied().ok_or(ProofError::MalformedProof),
+    }
+}
+
+fn parse_values(text: &[&str], types: &[AbiType]) -> Result<Vec<AbiValue>, AbiError> {
+    if text.len() != types.len() {
+        return Err(AbiError::WrongArity { expected: types.len(), found: text.len() });
+    }
+    text.iter().zip(types).map(|(text, ty)| AbiValue::parse(ty, text)).collect()
+}
+
+fn check_types(values: &[AbiValue], types: &[AbiType]) -> Result<(), AbiError> {
+    if values.len() != types.len() {
+        return Err(AbiError::WrongArity { expected: types.len(), found: values.len() });
+    }
+    match values.iter().zip(types).find(|(value, ty)| !value.fits(ty)) {
+        Some((value, ty)) => Err(AbiError::InvalidValue { ty: ty.to_string(), value: value.to_text() }),
+        None => Ok(()),
+    }
+}
+
+/// Flat tree over `leaves`, placed at the end in reverse
+fn make_tree(leaves: &[Digest]) -> Vec<Digest> {
+    let mut tree = vec![Digest::ZERO; 2 * leaves.len() - 1];
+    let size = tree.len();
+    for (i, leaf) in leaves.iter().enumerate() {
+        tree[size - 1 - i] = *leaf;
+    }
+    for i in (0..size - leaves.len()).rev() {
+        tree[i] = hash_pair(&tree[left_child(i)], &tree[left_child(i) + 1]);
+    }
+    tree
+}
+
+/// Whether every node with children is the hash of them
+fn is_valid_tree(tree: &[Digest]) -> bool {
+    !tree.is_empty() && (0..tree.len()).all(|i| {
+        let (left, right) = (left_child(i), left_child(i) + 1);
+        if right >= tree.len() {
+            return left >= tree.len();
+        }
+        tree[i] == hash_pair(&tree[left], &tree[right])
+    })
+}
+
+fn left_child(node: usize) -> usize {
+    2 * node + 1
+}
+
+fn parent(node: usize) -> usize {
+    (node - 1) / 2
+}
+
+fn sibling(node: usize) -> usize {
+    if node % 2 == 1 { node + 1 } else { node - 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example from the `@openzeppelin/merkle-tree` README
+    fn readme_tree() -> StandardMerkleTree {
+        StandardMerkleTree::from_text(&[
+            vec!["0x1111111111111111111111111111111111111111", "5000000000000000000"],
+            vec!["0x2222222222222222222222222222222222222222", "2500000000000000000"],
+        ], &["address", "uint256"]).unwrap()
+    }
+
+    #[test]
+    fn matches_the_readme_root() {
+        let root = Digest::from_hex("d4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77").unwrap();
+        assert_eq!(readme_tree().root(), root);
+    }
+
+    #[test]
+    fn proofs_verify_against_the_root() {
+        let tree = readme_tree();
+        for index in 0..tree.values.len() {
+            let leaf = tree.leaf_hash(index).unwrap();
+            let proof = tree.proof(index).unwrap();
+            assert!(verify(&tree.root(), &leaf, &proof));
+            assert!(!verify(&tree.root(), &hash_pair(&leaf, &leaf), &proof));
+        }
+
+        let multi = tree.multi_proof(&[0, 1]).unwrap();
+        assert_eq!(verify_multi(&tree.root(), &multi), Ok(true));
+    }
+
+    #[test]
+    fn dumps_load_back() {
+        let tree = readme_tree();
+        let loaded = StandardMerkleTree::load(&tree.dump()).unwrap();
+        assert_eq!(loaded.root(), tree.root());
+        assert_eq!(loaded.dump(), tree.dump());
+    }
+
+    #[test]
+    fn hex_values_take_only_hex_digits() {
+        assert!(AbiValue::parse(&AbiType::Bytes, "0x0f").is_ok());
+        assert!(AbiValue::parse(&AbiType::Bytes, "0x+f").is_err());
+        assert!(AbiValue::parse(&AbiType::FixedBytes(1), "0x-1").is_err());
+        assert!(AbiValue::parse(&AbiType::Address, "0x+111111111111111111111111111111111111111").is_err());
+    }
+}
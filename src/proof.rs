@@ -0,0 +1,828 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::MerkleError;
+use crate::hasher::{self, Digest, MerkleHasher};
+use crate::tree::{OddNodeRule, TreeOptions};
+
+/// Which side of the path a sibling sits on
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// One level of an inclusion proof: the sibling to hash the running value with, and on which side
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProofStep {
+    pub sibling: Digest,
+    pub side: Side,
+    /// Level of the sibling, leaves being level 1
+    pub level: u32,
+}
+
+/// Everything needed to check one leaf against a root, ordered from the leaf up.
+/// Levels where the node was promoted under `OddNodeRule::Promote` have no step.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InclusionProof {
+    pub leaf_index: usize,
+    pub tree_size: usize,
+    /// Root of the tree the proof was made from, to tell a stale proof from a wrong one
+    pub root: Digest,
+    pub steps: Vec<ProofStep>,
+    #[serde(skip)]
+    pub is_valid: bool,
+    /// Result of the last check of this proof, `None` until it has been checked
+    #[serde(skip)]
+    pub outcome: Option<Verification>,
+    pub options: TreeOptions
+}
+
+impl InclusionProof {
+    /// Level and index of each sibling in the tree, for highlighting the proof.
+    /// Siblings made up for an odd node point one past the end of their level.
+    pub fn sibling_positions(&self) -> Vec<(u32, usize)> {
+        self.steps.iter()
+            .map(|step| (step.level, (self.leaf_index >> (step.level - 1)) ^ 1))
+            .collect()
+    }
+
+    /// The siblings alone, from the leaf up. For a tree with `TreeOptions::sorted_pairs` this is all
+    /// `verify_sorted` needs, the form on-chain verifiers take.
+    pub fn siblings(&self) -> Vec<Digest> {
+        self.steps.iter().map(|step| step.sibling).collect()
+    }
+}
+
+/// Outcome of checking a proof against a root
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    Valid,
+    /// The proof is well formed but leads to a different root
+    RootMismatch,
+    /// The proof is for another leaf, or the leaf is outside the tree
+    WrongIndex,
+    /// The steps don't fit the path of the leaf through the tree
+    MalformedProof,
+    /// The proof was made before the tree changed
+    Stale,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        *self == Verification::Valid
+    }
+
+    /// Short description, shown next to the word in the GUI
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Verification::Valid => "valid",
+            Verification::RootMismatch => "root mismatch",
+            Verification::WrongIndex => "wrong index",
+            Verification::MalformedProof => "malformed proof",
+            Verification::Stale => "stale proof",
+        }
+    }
+}
+
+impl From<&MerkleError> for Verification {
+    fn from(error: &MerkleError) -> Self {
+        match error {
+            MerkleError::HashMismatch { .. } => Verification::RootMismatch,
+            MerkleError::IndexOutOfRange { .. } => Verification::WrongIndex,
+            MerkleError::StaleProof { .. } => Verification::Stale,
+            MerkleError::EmptyTree | MerkleError::MissingLevel(_) | MerkleError::MalformedProof
+                | MerkleError::WordIncluded { .. } | MerkleError::Unsorted | MerkleError::InvalidHex(_) => Verification::MalformedProof,
+        }
+    }
+}
+
+impl From<Result<bool, ProofError>> for Verification {
+    fn from(result: Result<bool, ProofError>) -> Self {
+        match result {
+            Ok(true) => Verification::Valid,
+            Ok(false) => Verification::RootMismatch,
+            Err(ProofError::IndexMismatch { .. }) | Err(ProofError::IndexOutOfRange { .. }) => Verification::WrongIndex,
+            Err(ProofError::MalformedProof) => Verification::MalformedProof,
+        }
+    }
+}
+
+/// Why a proof could not be checked at all, as opposed to checking out to a different root
+#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// The steps don't match the path of `leaf_index` through a tree of `tree_size` leaves
+    MalformedProof,
+    /// The proof was made for a different leaf than the one being checked
+    IndexMismatch { expected: usize, found: usize },
+    /// The leaf index doesn't exist in a tree of `tree_size` leaves
+    IndexOutOfRange { index: usize, tree_size: usize },
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::MalformedProof => write!(f, "malformed proof"),
+            ProofError::IndexMismatch { expected, found } => write!(f, "proof is for leaf {}, not {}", expected, found),
+            ProofError::IndexOutOfRange { index, tree_size } => write!(f, "leaf {} is out of range for a tree of {} leaves", index, tree_size),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+/// The (level, side) of every step a proof for `index` must have in a tree of `tree_size` leaves
+pub fn path_shape(index: usize, tree_size: usize, odd_rule: OddNodeRule) -> Vec<(u32, Side)> {
+    let mut shape = Vec::new();
+    let mut node_index = index;
+    let mut level_size = tree_size;
+    let mut level = 1;
+
+    while level_size > 1 {
+        let side = if node_index.is_multiple_of(2) { Side::Right } else { Side::Left };
+        if (node_index ^ 1) < level_size || odd_rule != OddNodeRule::Promote {
+            shape.push((level, side));
+        }
+        node_index /= 2;
+        level_size = level_size.div_ceil(2);
+        level += 1;
+    }
+    shape
+}
+
+/// Check `proof` for the leaf hash `leaf` at `index` against a known `root`, without the tree.
+///
+/// `leaf` is the leaf's digest as produced by `hasher::hash_leaf` with `proof.options`.
+/// Returns `Ok(false)` when the proof is well formed but leads to a different root.
+pub fn verify<H: MerkleHasher>(root: &Digest, leaf: &Digest, index: usize, proof: &InclusionProof, hasher: &H) -> Result<bool, ProofError> {
+    Ok(compute_root(leaf, index, proof, hasher)? == *root)
+}
+
+/// The root `proof` leads to from the leaf hash `leaf` at `index`
+pub fn compute_root<H: MerkleHasher>(leaf: &Digest, index: usize, proof: &InclusionProof, hasher: &H) -> Result<Digest, ProofError> {
+    if index != proof.leaf_index {
+        return Err(ProofError::IndexMismatch { expected: proof.leaf_index, found: index });
+    }
+    check_shape(proof)?;
+
+    let mut current_hash = *leaf;
+    for step in &proof.steps {
+        current_hash = match step.side {
+            Side::Right => hasher::hash_combination(hasher, &current_hash, &step.sibling, &proof.options),
+            Side::Left => hasher::hash_combination(hasher, &step.sibling, &current_hash, &proof.options),
+        };
+    }
+
+    Ok(current_hash)
+}
+
+/// Check siblings from `InclusionProof::siblings` against `root` without the leaf's index.
+/// Only trees built with `TreeOptions::sorted_pairs` can be checked this way.
+pub fn verify_sorted<H: MerkleHasher>(root: &Digest, leaf: &Digest, siblings: &[Digest], hasher: &H, options: &TreeOptions) -> Result<bool, ProofError> {
+    Ok(compute_sorted_root(leaf, siblings, hasher, options)? == *root)
+}
+
+/// The root `siblings` lead to from the leaf hash `leaf`, hashing each pair in ascending order
+pub fn compute_sorted_root<H: MerkleHasher>(leaf: &Digest, siblings: &[Digest], hasher: &H, options: &TreeOptions) -> Result<Digest, ProofError> {
+    if !options.sorted_pairs {
+        return Err(ProofError::MalformedProof);
+    }
+    Ok(siblings.iter().fold(*leaf, |hash, sibling| hasher::hash_combination(hasher, &hash, sibling, options)))
+}
+
+/// Check that the proof's steps are the ones its leaf index and tree size call for
+pub fn check_shape(proof: &InclusionProof) -> Result<(), ProofError> {
+    if proof.leaf_index >= proof.tree_size {
+        return Err(ProofError::IndexOutOfRange { index: proof.leaf_index, tree_size: proof.tree_size });
+    }
+
+    let shape = path_shape(proof.leaf_index, proof.tree_size, proof.options.odd_rule);
+    if shape.len() != proof.steps.len()
+        || shape.iter().zip(proof.steps.iter()).any(|(&(level, side), step)| step.level != level || step.side != side) {
+        return Err(ProofError::MalformedProof);
+    }
+    Ok(())
+}
+
+/// Like `verify`, but folds errors into a `Verification` outcome
+pub fn check<H: MerkleHasher>(root: &Digest, leaf: &Digest, index: usize, proof: &InclusionProof, hasher: &H) -> Verification {
+    Verification::from(verify(root, leaf, index, proof, hasher))
+}
+
+/// A leaf next to where an excluded leaf would sit, with its inclusion proof
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct Neighbour {
+    /// Label of the leaf, for display
+    pub word: String,
+    /// The encoded leaf its hash is checked against
+    pub value: Vec<u8>,
+    pub proof: InclusionProof,
+}
+
+/// Proof that a leaf is not in a tree with sorted leaves: the two adjacent leaves it would sit
+/// between. `left` is missing when the leaf sorts before the first one, `right` when it sorts
+/// after the last.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct ExclusionProof {
+    pub left: Option<Neighbour>,
+    pub right: Option<Neighbour>,
+    pub is_valid: bool,
+    /// Result of the last check of this proof, `None` until it has been checked
+    pub outcome: Option<Verification>,
+}
+
+/// Check that the encoded leaf `value` is not in the tree with root `root`: both neighbours must be
+/// proven, adjacent, and sort one below and one above `value`.
+/// Returns `Ok(false)` when a neighbour proof leads to another root or the neighbours don't bracket `value`.
+pub fn verify_exclusion<H: MerkleHasher>(root: &Digest, value: &[u8], proof: &ExclusionProof, hasher: &H) -> Result<bool, ProofError> {
+    let (options, tree_size) = match (&proof.left, &proof.right) {
+        (Some(neighbour), _) | (None, Some(neighbour)) => (neighbour.proof.options, neighbour.proof.tree_size),
+        (None, None) => return Err(ProofError::MalformedProof),
+    };
+    let neighbours: Vec<&Neighbour> = [&proof.left, &proof.right].into_iter().flatten().collect();
+    if !options.leaf_order.is_sorted()
+        || neighbours.iter().any(|neighbour| neighbour.proof.options != options || neighbour.proof.tree_size != tree_size) {
+        return Err(ProofError::MalformedProof);
+    }
+
+    // nothing may fit between the neighbours, or past the end of the tree
+    let adjacent = match (&proof.left, &proof.right) {
+        (Some(left), Some(right)) => right.proof.leaf_index == left.proof.leaf_index + 1,
+        (None, Some(right)) => right.proof.leaf_index == 0,
+        (Some(left), None) => left.proof.leaf_index + 1 == tree_size,
+        (None, None) => false,
+    };
+    if !adjacent {
+        return Err(ProofError::MalformedProof);
+    }
+
+    for neighbour in &neighbours {
+        let leaf = hasher::hash_leaf(hasher, &neighbour.value, &options);
+        if !verify(root, &leaf, neighbour.proof.leaf_index, &neighbour.proof, hasher)? {
+            return Ok(false);
+        }
+    }
+
+    let hash = hasher::hash_leaf(hasher, value, &options);
+    let key = options.leaf_order.sort_key(value, &hash);
+    let below = proof.left.as_ref().is_none_or(|left| {
+        let left_hash = hasher::hash_leaf(hasher, &left.value, &options);
+        options.leaf_order.sort_key(&left.value, &left_hash) < key
+    });
+    let above = proof.right.as_ref().is_none_or(|right| {
+        let right_hash = hasher::hash_leaf(hasher, &right.value, &options);
+        options.leaf_order.sort_key(&right.value, &right_hash) > key
+    });
+    Ok(below && above)
+}
+
+/// Like `verify_exclusion`, but folds errors into a `Verification` outcome
+pub fn check_exclusion<H: MerkleHasher>(root: &Digest, value: &[u8], proof: &ExclusionProof, hasher: &H) -> Verification {
+    Verification::from(verify_exclusion(root, value, proof, hasher))
+}
+
+/// Proof that a tree of `old_size` leaves is a prefix of a tree of `new_size` leaves built with the
+/// same options, as in RFC 6962 section 2.1.2.
+///
+/// `hashes` runs from the bottom up: the largest complete subtree ending at the last old leaf
+/// (left out when that is the whole old tree), then the siblings on its path to the new root.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct ConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub hashes: Vec<Digest>,
+    pub options: TreeOptions
+}
+
+/// Level and index of the first node of a consistency proof, the largest complete subtree ending at
+/// leaf `old_size - 1`, and whether it is the whole old tree
+pub fn consistency_start(old_size: usize) -> (u32, usize, bool) {
+    let level = old_size.trailing_zeros() + 1;
+    (level, (old_size - 1) >> (level - 1), old_size.is_power_of_two())
+}
+
+/// Check that the tree with root `old_root` is a prefix of the tree with root `new_root`
+pub fn verify_consistency<H: MerkleHasher>(old_root: &Digest, new_root: &Digest, proof: &ConsistencyProof, hasher: &H) -> Result<bool, ProofError> {
+    let (old_computed, new_computed) = compute_consistency_roots(old_root, proof, hasher)?;
+    Ok(old_computed == *old_root && new_computed == *new_root)
+}
+
+/// The old and new roots a consistency proof leads to. `old_root` is only used as the starting
+/// point when the old tree is a complete subtree of the new one.
+pub fn compute_consistency_roots<H: MerkleHasher>(old_root: &Digest, proof: &ConsistencyProof, hasher: &H) -> Result<(Digest, Digest), ProofError> {
+    let (old_size, new_size) = (proof.old_size, proof.new_size);
+    if old_size == 0 || old_size > new_size {
+        return Err(ProofError::IndexOutOfRange { index: old_size, tree_size: new_size });
+    }
+    if old_size == new_size {
+        return match proof.hashes.is_empty() {
+            true => Ok((*old_root, *old_root)),
+            false => Err(ProofError::MalformedProof),
+        };
+    }
+
+    let mut hashes = proof.hashes.iter();
+    let (start_level, _, is_old_root) = consistency_start(old_size);
+    let start = if is_old_root { *old_root } else { *hashes.next().ok_or(ProofError::MalformedProof)? };
+    let (mut old_hash, mut new_hash) = (start, start);
+
+    let options = &proof.options;
+    // the hash a node without a sibling turns into on the next level
+    let odd_node = |hash: Digest| match options.odd_rule {
+        OddNodeRule::Duplicate => hasher::hash_combination(hasher, &hash, &hash, options),
+        OddNodeRule::Promote => hash,
+        OddNodeRule::PadEmpty => hasher::hash_combination(hasher, &hash, &hasher::EMPTY_HASH, options),
+    };
+
+    let shift = start_level - 1;
+    let mut old_level_size = old_size.div_ceil(1 << shift);
+    let mut new_level_size = new_size.div_ceil(1 << shift);
+    let mut node_index = (old_size - 1) >> shift;
+
+    while new_level_size > 1 {
+        if !node_index.is_multiple_of(2) {
+            // a left sibling lies inside the old tree, so both trees share it
+            let sibling = hashes.next().ok_or(ProofError::MalformedProof)?;
+            old_hash = hasher::hash_combination(hasher, sibling, &old_hash, options);
+            new_hash = hasher::hash_combination(hasher, sibling, &new_hash, options);
+        } else {
+            // the path node is the last of its level in the old tree
+            if old_level_size > 1 {
+                old_hash = odd_node(old_hash);
+            }
+            new_hash = if node_index + 1 < new_level_size {
+                let sibling = hashes.next().ok_or(ProofError::MalformedProof)?;
+                hasher::hash_combination(hasher, &new_hash, sibling, options)
+            } else {
+                odd_node(new_hash)
+            };
+        }
+        node_index /= 2;
+        old_level_size = old_level_size.div_ceil(2);
+        new_level_size = new_level_size.div_ceil(2);
+    }
+
+    if hashes.next().is_some() {
+        return Err(ProofError::MalformedProof);
+    }
+    Ok((old_hash, new_hash))
+}
+
+/// One proof for several leaves at once, in the layout of OpenZeppelin's `processMultiProof`.
+///
+/// Known hashes (the leaves, then every parent computed from them) are used in a queue. Each flag is
+/// one hash: `true` pairs the next two known hashes, `false` pairs the next known hash with the next
+/// entry of `proof`. Leaves are taken in ascending index order and each level is finished before the
+/// next, so siblings shared by several leaves appear once.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MultiProof {
+    /// Leaves covered by the proof, ascending and without duplicates
+    pub leaf_indices: Vec<usize>,
+    pub tree_size: usize,
+    /// Root of the tree the proof was made from, to tell a stale proof from a wrong one
+    pub root: Digest,
+    /// Auxiliary hashes, in the order they are used
+    pub proof: Vec<Digest>,
+    pub proof_flags: Vec<bool>,
+    #[serde(skip)]
+    pub is_valid: bool,
+    /// Result of the last check of this proof, `None` until it has been checked
+    #[serde(skip)]
+    pub outcome: Option<Verification>,
+    pub options: TreeOptions
+}
+
+impl MultiProof {
+    /// Level and index of each auxiliary hash in the tree, for highlighting the proof.
+    /// Siblings made up for an odd node point one past the end of their level.
+    pub fn sibling_positions(&self) -> Vec<(u32, usize)> {
+        multi_path_shape(&self.leaf_indices, self.tree_size, self.options.odd_rule)
+            .into_iter()
+            .filter_map(|step| match step {
+                MultiStep::Sibling { level, index, .. } => Some((level, index ^ 1)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// How a known node reaches the next level of a multiproof
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum MultiStep {
+    /// Hashed with the next known node, its right sibling (flag `true`)
+    Pair { level: u32, index: usize },
+    /// Hashed with the next auxiliary hash, which sits on `side` (flag `false`)
+    Sibling { level: u32, index: usize, side: Side },
+    /// Carried up unchanged under `OddNodeRule::Promote`, no flag
+    Promote { level: u32, index: usize },
+}
+
+/// The steps a multiproof for `leaf_indices` takes in a tree of `tree_size` leaves, in proof order.
+/// `leaf_indices` must be ascending, without duplicates and inside the tree.
+pub fn multi_path_shape(leaf_indices: &[usize], tree_size: usize, odd_rule: OddNodeRule) -> Vec<MultiStep> {
+    let mut shape = Vec::new();
+    let mut known = leaf_indices.to_vec();
+    let mut level_size = tree_size;
+    let mut level = 1;
+
+    while level_size > 1 {
+        let mut parents = Vec::new();
+        let mut i = 0;
+        while i < known.len() {
+            let index = known[i];
+            let sibling_index = index ^ 1;
+            if index.is_multiple_of(2) && known.get(i + 1) == Some(&sibling_index) {
+                shape.push(MultiStep::Pair { level, index });
+                i += 1;
+            } else if sibling_index >= level_size && odd_rule == OddNodeRule::Promote {
+                shape.push(MultiStep::Promote { level, index });
+            } else {
+                let side = if index.is_multiple_of(2) { Side::Right } else { Side::Left };
+                shape.push(MultiStep::Sibling { level, index, side });
+            }
+            parents.push(index / 2);
+            i += 1;
+        }
+        known = parents;
+        level_size = level_size.div_ceil(2);
+        level += 1;
+    }
+    shape
+}
+
+/// Check a multiproof for the leaf hashes `leaves`, given in the order of `proof.leaf_indices`,
+/// against a known `root`
+pub fn verify_multi<H: MerkleHasher>(root: &Digest, leaves: &[Digest], proof: &MultiProof, hasher: &H) -> Result<bool, ProofError> {
+    Ok(compute_multi_root(leaves, proof, hasher)? == *root)
+}
+
+/// The root a multiproof leads to from the leaf hashes `leaves`.
+///
+/// The leaf indices are needed to know which side each hash goes on, the flags must match them.
+pub fn compute_multi_root<H: MerkleHasher>(leaves: &[Digest], proof: &MultiProof, hasher: &H) -> Result<Digest, ProofError> {
+    if leaves.len() != proof.leaf_indices.len() {
+        return Err(ProofError::MalformedProof);
+    }
+    let shape = check_multi_shape(proof)?;
+
+    let mut queue: VecDeque<Digest> = leaves.iter().copied().collect();
+    let mut auxiliary = proof.proof.iter();
+    for step in shape {
+        // the shape matches the flags, so the queue and the auxiliary hashes can't run out
+        let known = queue.pop_front().ok_or(ProofError::MalformedProof)?;
+        let parent = match step {
+            MultiStep::Pair { .. } => {
+                let right = queue.pop_front().ok_or(ProofError::MalformedProof)?;
+                hasher::hash_combination(hasher, &known, &right, &proof.options)
+            }
+            MultiStep::Sibling { side, .. } => {
+                let sibling = auxiliary.next().ok_or(ProofError::MalformedProof)?;
+                match side {
+                    Side::Right => hasher::hash_combination(hasher, &known, sibling, &proof.options),
+                    Side::Left => hasher::hash_combination(hasher, sibling, &known, &proof.options),
+                }
+            }
+            MultiStep::Promote { .. } => known,
+        };
+        queue.push_back(parent);
+    }
+
+    match (queue.pop_front(), queue.is_empty()) {
+        (Some(root), true) => Ok(root),
+        _ => Err(ProofError::MalformedProof),
+    }
+}
+
+/// Check that the leaf indices are ascending and inside the tree, and that the flags and auxiliary
+/// hashes are the ones they call for. Returns the steps of the proof.
+pub fn check_multi_shape(proof: &MultiProof) -> Result<Vec<MultiStep>, ProofError> {
+    if proof.leaf_indices.is_empty() || proof.leaf_indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(ProofError::MalformedProof);
+    }
+    if let Some(&index) = proof.leaf_indices.iter().find(|&&index| index >= proof.tree_size) {
+        return Err(ProofError::IndexOutOfRange { index, tree_size: proof.tree_size });
+    }
+
+    let shape = multi_path_shape(&proof.leaf_indices, proof.tree_size, proof.options.odd_rule);
+    let flags: Vec<bool> = shape.iter()
+        .filter_map(|step| match step {
+            MultiStep::Pair { .. } => Some(true),
+            MultiStep::Sibling { .. } => Some(false),
+            MultiStep::Promote { .. } => None,
+        })
+        .collect();
+    if flags != proof.proof_flags || flags.iter().filter(|&&flag| !flag).count() != proof.proof.len() {
+        return Err(ProofError::MalformedProof);
+    }
+    Ok(shape)
+}
+
+/// Like `verify_multi`, but folds errors into a `Verification` outcome
+pub fn check_multi<H: MerkleHasher>(root: &Digest, leaves: &[Digest], proof: &MultiProof, hasher: &H) -> Verification {
+    Verification::from(verify_multi(root, leaves, proof, hasher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::{Keccak256, Sha256};
+    use crate::tree::{self, LeafOrder, MerkleTree};
+
+    const WORDS: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+    fn tree_with(options: TreeOptions) -> MerkleTree<Keccak256> {
+        tree::build_tree_with(WORDS, Keccak256, options).unwrap()
+    }
+
+    fn leaf_hash(tree: &MerkleTree<Keccak256>, index: usize) -> Digest {
+        tree.nodes[&1][index].hash
+    }
+
+    /// Root, leaf hash and proof of the word at `index`
+    fn proven(index: usize) -> (Digest, Digest, InclusionProof) {
+        let mut tree = tree_with(TreeOptions::default());
+        let proof = tree.inclusion_proof(index).unwrap();
+        (tree.root_hash().unwrap(), leaf_hash(&tree, index), proof)
+    }
+
+    #[test]
+    fn untouched_proofs_verify() {
+        for odd_rule in [OddNodeRule::Duplicate, OddNodeRule::Promote, OddNodeRule::PadEmpty] {
+            let mut tree = tree_with(TreeOptions { odd_rule, ..Default::default() });
+            let root = tree.root_hash().unwrap();
+            for index in 0..WORDS.len() {
+                let proof = tree.inclusion_proof(index).unwrap();
+                let leaf = leaf_hash(&tree, index);
+                assert_eq!(verify(&root, &leaf, index, &proof, &Keccak256), Ok(true));
+                assert_eq!(check(&root, &leaf, index, &proof, &Keccak256), Verification::Valid);
+            }
+        }
+    }
+
+    #[test]
+    fn checking_another_index_is_a_mismatch() {
+        let (root, leaf, proof) = proven(2);
+        assert_eq!(verify(&root, &leaf, 3, &proof, &Keccak256), Err(ProofError::IndexMismatch { expected: 2, found: 3 }));
+        assert_eq!(check(&root, &leaf, 3, &proof, &Keccak256), Verification::WrongIndex);
+    }
+
+    #[test]
+    fn tampered_leaf_index_is_rejected() {
+        let (root, leaf, mut proof) = proven(2);
+        // leaf 3 sits on the other side of its sibling
+        proof.leaf_index = 3;
+        assert_eq!(verify(&root, &leaf, 3, &proof, &Keccak256), Err(ProofError::MalformedProof));
+
+        proof.leaf_index = 5;
+        assert_eq!(verify(&root, &leaf, 5, &proof, &Keccak256), Err(ProofError::IndexOutOfRange { index: 5, tree_size: 5 }));
+        assert_eq!(check(&root, &leaf, 5, &proof, &Keccak256), Verification::WrongIndex);
+    }
+
+    #[test]
+    fn tampered_tree_size_is_rejected() {
+        let (root, leaf, mut proof) = proven(2);
+        proof.tree_size = 2;
+        assert_eq!(check_shape(&proof), Err(ProofError::IndexOutOfRange { index: 2, tree_size: 2 }));
+
+        // a tree of 16 leaves is one level higher
+        proof.tree_size = 16;
+        assert_eq!(verify(&root, &leaf, 2, &proof, &Keccak256), Err(ProofError::MalformedProof));
+    }
+
+    #[test]
+    fn tampered_steps_are_rejected() {
+        let (root, leaf, proof) = proven(2);
+
+        let mut flipped = proof.clone();
+        flipped.steps[0].side = Side::Left;
+        assert_eq!(verify(&root, &leaf, 2, &flipped, &Keccak256), Err(ProofError::MalformedProof));
+        assert_eq!(check(&root, &leaf, 2, &flipped, &Keccak256), Verification::MalformedProof);
+
+        let mut relevelled = proof.clone();
+        relevelled.steps[1].level = 3;
+        assert_eq!(verify(&root, &leaf, 2, &relevelled, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut shortened = proof.clone();
+        shortened.steps.pop();
+        assert_eq!(check_shape(&shortened), Err(ProofError::MalformedProof));
+
+        let mut resibled = proof;
+        resibled.steps[0].sibling = leaf;
+        assert_eq!(verify(&root, &leaf, 2, &resibled, &Keccak256), Ok(false));
+    }
+
+    #[test]
+    fn another_root_does_not_verify() {
+        let (root, leaf, proof) = proven(2);
+        let other = tree::build_tree_with(["a", "b", "x", "d", "e"], Keccak256, TreeOptions::default()).unwrap().root_hash().unwrap();
+        assert_ne!(root, other);
+        assert_eq!(verify(&other, &leaf, 2, &proof, &Keccak256), Ok(false));
+        assert_eq!(check(&other, &leaf, 2, &proof, &Keccak256), Verification::RootMismatch);
+    }
+
+    #[test]
+    fn sorted_roots_need_sorted_pairs() {
+        let (root, leaf, proof) = proven(2);
+        assert_eq!(verify_sorted(&root, &leaf, &proof.siblings(), &Keccak256, &TreeOptions::default()), Err(ProofError::MalformedProof));
+
+        let options = TreeOptions { sorted_pairs: true, ..Default::default() };
+        let mut tree = tree_with(options);
+        let proof = tree.inclusion_proof(2).unwrap();
+        let leaf = leaf_hash(&tree, 2);
+        assert_eq!(verify_sorted(&tree.root_hash().unwrap(), &leaf, &proof.siblings(), &Keccak256, &options), Ok(true));
+    }
+
+    #[test]
+    fn tampered_multiproofs_are_rejected() {
+        let mut tree = tree_with(TreeOptions::default());
+        let root = tree.root_hash().unwrap();
+        let proof = tree.multi_proof(&[1, 3, 4]).unwrap();
+        let leaves: Vec<Digest> = [1, 3, 4].iter().map(|index| leaf_hash(&tree, *index)).collect();
+        assert_eq!(verify_multi(&root, &leaves, &proof, &Keccak256), Ok(true));
+
+        assert_eq!(verify_multi(&root, &leaves[..2], &proof, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut unordered = proof.clone();
+        unordered.leaf_indices = vec![3, 1, 4];
+        assert_eq!(check_multi_shape(&unordered), Err(ProofError::MalformedProof));
+
+        let mut outside = proof.clone();
+        outside.leaf_indices = vec![1, 3, 7];
+        assert_eq!(check_multi_shape(&outside), Err(ProofError::IndexOutOfRange { index: 7, tree_size: 5 }));
+
+        let mut reflagged = proof.clone();
+        reflagged.proof_flags[0] = !reflagged.proof_flags[0];
+        assert_eq!(verify_multi(&root, &leaves, &reflagged, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut extra = proof.clone();
+        extra.proof.push(root);
+        assert_eq!(check_multi(&root, &leaves, &extra, &Keccak256), Verification::MalformedProof);
+
+        let mut swapped = leaves.clone();
+        swapped.swap(0, 1);
+        assert_eq!(verify_multi(&root, &swapped, &proof, &Keccak256), Ok(false));
+    }
+
+    #[test]
+    fn tampered_consistency_proofs_are_rejected() {
+        let old = tree::build_tree_with(&WORDS[..3], Keccak256, TreeOptions::default()).unwrap();
+        let new = tree_with(TreeOptions::default());
+        let (old_root, new_root) = (old.root_hash().unwrap(), new.root_hash().unwrap());
+        let proof = new.consistency_proof(3).unwrap();
+        assert_eq!(verify_consistency(&old_root, &new_root, &proof, &Keccak256), Ok(true));
+        assert_eq!(verify_consistency(&new_root, &new_root, &proof, &Keccak256), Ok(false));
+
+        let mut empty = proof.clone();
+        empty.old_size = 0;
+        assert_eq!(verify_consistency(&old_root, &new_root, &empty, &Keccak256), Err(ProofError::IndexOutOfRange { index: 0, tree_size: 5 }));
+
+        let mut larger = proof.clone();
+        larger.old_size = 6;
+        assert_eq!(verify_consistency(&old_root, &new_root, &larger, &Keccak256), Err(ProofError::IndexOutOfRange { index: 6, tree_size: 5 }));
+
+        let mut short = proof.clone();
+        short.hashes.pop();
+        assert_eq!(verify_consistency(&old_root, &new_root, &short, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut long = proof.clone();
+        long.hashes.push(new_root);
+        assert_eq!(verify_consistency(&old_root, &new_root, &long, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut same = proof;
+        same.old_size = 5;
+        assert_eq!(verify_consistency(&new_root, &new_root, &same, &Keccak256), Err(ProofError::MalformedProof));
+    }
+
+    /// The leaves of the RFC 6962 test vectors used by Certificate Transparency, as hex
+    const RFC6962_LEAVES: [&str; 8] = ["", "00", "10", "2021", "3031", "40414243", "5051525354555657", "606162636465666768696a6b6c6d6e6f"];
+
+    fn rfc6962_options() -> TreeOptions {
+        TreeOptions { odd_rule: OddNodeRule::Promote, domain_separation: true, ..Default::default() }
+    }
+
+    fn rfc6962_tree(size: usize) -> MerkleTree<Sha256> {
+        let leaves: Vec<Vec<u8>> = RFC6962_LEAVES[..size].iter()
+            .map(|hex| (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect())
+            .collect();
+        tree::build_tree_with(leaves, Sha256, rfc6962_options()).unwrap()
+    }
+
+    #[test]
+    fn rfc6962_roots_and_consistency_proofs() {
+        let roots = [
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        for (size, root) in (1..=8).zip(roots) {
+            assert_eq!(rfc6962_tree(size).root_hash().unwrap().to_hex(), root, "{} leaves", size);
+        }
+
+        let proofs: [(usize, usize, &[&str]); 3] = [
+            (1, 8, &[
+                "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+            ]),
+            (6, 8, &[
+                "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+                "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+                "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            ]),
+            (2, 5, &[
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            ]),
+        ];
+        for (old_size, new_size, hashes) in proofs {
+            let proof = rfc6962_tree(new_size).consistency_proof(old_size).unwrap();
+            let found: Vec<String> = proof.hashes.iter().map(Digest::to_hex).collect();
+            assert_eq!(found, hashes, "{} -> {}", old_size, new_size);
+        }
+    }
+
+    #[test]
+    fn consistency_proofs_verify_for_every_size() {
+        for odd_rule in [OddNodeRule::Duplicate, OddNodeRule::Promote, OddNodeRule::PadEmpty] {
+            let options = TreeOptions { odd_rule, ..Default::default() };
+            let words: Vec<String> = (0..17).map(|i| format!("word {}", i)).collect();
+            let trees: Vec<MerkleTree<Keccak256>> = (1..=words.len())
+                .map(|size| tree::build_tree_with(&words[..size], Keccak256, options).unwrap())
+                .collect();
+            let roots: Vec<Digest> = trees.iter().map(|tree| tree.root_hash().unwrap()).collect();
+
+            for new_size in 1..=trees.len() {
+                let new = &trees[new_size - 1];
+                for old_size in 1..=new_size {
+                    let proof = new.consistency_proof(old_size).unwrap();
+                    let old_root = &roots[old_size - 1];
+                    assert_eq!(verify_consistency(old_root, &roots[new_size - 1], &proof, &Keccak256), Ok(true),
+                        "{:?} {} -> {}", odd_rule, old_size, new_size);
+                    assert_eq!(new.validate_consistency(old_root, old_size), Ok(()));
+                    if old_size < new_size {
+                        assert_eq!(verify_consistency(&roots[new_size - 1], &roots[new_size - 1], &proof, &Keccak256), Ok(false));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_exclusion_proofs_are_rejected() {
+        let options = TreeOptions { leaf_order: LeafOrder::ByValue, ..Default::default() };
+        let mut tree = tree::build_tree_with(["a", "c", "e", "g"], Keccak256, options).unwrap();
+        let root = tree.root_hash().unwrap();
+        let proof = tree.exclusion_proof(b"d").unwrap();
+        assert_eq!(verify_exclusion(&root, b"d", &proof, &Keccak256), Ok(true));
+        // "f" doesn't fit between "c" and "e"
+        assert_eq!(verify_exclusion(&root, b"f", &proof, &Keccak256), Ok(false));
+
+        assert_eq!(verify_exclusion(&root, b"d", &ExclusionProof::default(), &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut gap = proof.clone();
+        gap.right = Some(Neighbour { proof: tree.inclusion_proof(3).unwrap(), value: b"g".to_vec(), word: "g".to_string() });
+        assert_eq!(verify_exclusion(&root, b"d", &gap, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut unsorted = proof.clone();
+        for neighbour in [&mut unsorted.left, &mut unsorted.right].into_iter().flatten() {
+            neighbour.proof.options.leaf_order = LeafOrder::AsGiven;
+        }
+        assert_eq!(check_exclusion(&root, b"d", &unsorted, &Keccak256), Verification::MalformedProof);
+
+        let mut relabelled = proof;
+        if let Some(left) = relabelled.left.as_mut() {
+            left.value = b"b".to_vec();
+        }
+        assert_eq!(check_exclusion(&root, b"d", &relabelled, &Keccak256), Verification::RootMismatch);
+    }
+}
@@ -0,0 +1,308 @@
+//! Sparse Merkle tree of depth 256 for key-value commitments.
+//!
+//! A key's leaf sits at the path given by the bits of `hasher::keccak_256(key)`, most significant
+//! bit first from the root. Empty subtrees hash to precomputed defaults, so only the nodes above
+//! set keys are stored.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::hasher::{self, Digest, Keccak256, MerkleHasher};
+use crate::proof::{ProofError, ProofStep, Side};
+use crate::tree::TreeOptions;
+
+/// Number of levels between a leaf and the root
+pub const DEPTH: u32 = 256;
+
+/// Proof that a key holds a value, or holds nothing, in a sparse tree.
+/// Steps whose sibling is an empty subtree are left out, the verifier fills in the defaults.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct SparseProof {
+    /// `hasher::keccak_256` of the key, the leaf's path from the root
+    pub path: Digest,
+    /// Non-empty siblings from the leaf up, leaves being level 1
+    pub steps: Vec<ProofStep>,
+    pub options: TreeOptions
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SparseMerkleTree<H: MerkleHasher = Keccak256> {
+    pub hasher: H,
+    pub options: TreeOptions,
+    /// Values by key path
+    pub values: BTreeMap<Digest, Vec<u8>>,
+    /// `defaults[h]` is the hash of an empty subtree of height `h`, leaves having height 0
+    defaults: Vec<Digest>,
+    /// Non-empty nodes by height and path, with the bits below the node cleared
+    nodes: HashMap<(u32, Digest), Digest>,
+}
+
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        SparseMerkleTree::new(Keccak256, TreeOptions::default())
+    }
+}
+
+impl<H: MerkleHasher> SparseMerkleTree<H> {
+    pub fn new(hasher: H, options: TreeOptions) -> SparseMerkleTree<H> {
+        let defaults = default_hashes(&hasher, &options);
+        SparseMerkleTree {
+            hasher,
+            options,
+            values: BTreeMap::new(),
+            defaults,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> Digest {
+        self.node(DEPTH, &Digest::ZERO)
+    }
+
+    /// Hash of an empty subtree `height` levels above the leaves
+    pub fn default_hash(&self, height: u32) -> Digest {
+        self.defaults[height as usize]
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.values.get(&hasher::keccak_256(key)).map(|value| value.as_slice())
+    }
+
+    /// Set `key` to `value`, rehashing the 256 nodes above it
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let path = hasher::keccak_256(key);
+        let leaf = hasher::hash_leaf(&self.hasher, value, &self.options);
+        self.values.insert(path, value.to_vec());
+        self.set_leaf(path, leaf);
+    }
+
+    /// Empty the leaf of `key`, returning the value it held
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let path = hasher::keccak_256(key);
+        let value = self.values.remove(&path)?;
+        self.set_leaf(path, self.defaults[0]);
+        Some(value)
+    }
+
+    /// Proof for `key`: of membership if it holds a value, of non-membership otherwise
+    pub fn prove(&self, key: &[u8]) -> SparseProof {
+        let path = hasher::keccak_256(key);
+        let mut steps = Vec::new();
+        for height in 0..DEPTH {
+            let sibling_path = flip_bit(&prefix(&path, height), height);
+            let sibling = self.node(height, &sibling_path);
+            if sibling != self.defaults[height as usize] {
+                steps.push(ProofStep { sibling, side: sibling_side(&path, height), level: height + 1 });
+            }
+        }
+        SparseProof { path, steps, options: self.options }
+    }
+
+    fn node(&self, height: u32, path: &Digest) -> Digest {
+        self.nodes.get(&(height, *path)).copied().unwrap_or(self.defaults[height as usize])
+    }
+
+    fn set_leaf(&mut self, path: Digest, leaf: Digest) {
+        let mut hash = leaf;
+        for height in 0..=DEPTH {
+            let node_path = prefix(&path, height);
+            // empty subtrees are implied by the defaults
+            if hash == self.defaults[height as usize] {
+                self.nodes.remove(&(height, node_path));
+            } else {
+                self.nodes.insert((height, node_path), hash);
+            }
+            if height == DEPTH {
+                break;
+            }
+
+            let sibling = self.node(height, &flip_bit(&node_path, height));
+            hash = match sibling_side(&path, height) {
+                Side::Right => hasher::hash_combination(&self.hasher, &hash, &sibling, &self.options),
+                Side::Left => hasher::hash_combination(&self.hasher, &sibling, &hash, &self.options),
+            };
+        }
+    }
+}
+
+/// Hashes of empty subtrees of every height, from an empty leaf (`hasher::EMPTY_HASH`) to the root
+pub fn default_hashes<H: MerkleHasher>(hasher: &H, options: &TreeOptions) -> Vec<Digest> {
+    let mut defaults = vec![hasher::EMPTY_HASH];
+    for height in 0..DEPTH as usize {
+        let below = defaults[height];
+        defaults.push(hasher::hash_combination(hasher, &below, &below, options));
+    }
+    defaults
+}
+
+/// Check `proof` against `root`. `value` is what the key should hold, `None` to prove it holds nothing.
+pub fn verify<H: MerkleHasher>(root: &Digest, key: &[u8], value: Option<&[u8]>, proof: &SparseProof, hasher: &H) -> Result<bool, ProofError> {
+    Ok(compute_root(key, value, proof, hasher)? == *root)
+}
+
+/// The root `proof` leads to from `value` at `key`
+pub fn compute_root<H: MerkleHasher>(key: &[u8], value: Option<&[u8]>, proof: &SparseProof, hasher: &H) -> Result<Digest, ProofError> {
+    let path = hasher::keccak_256(key);
+    if path != proof.path {
+        return Err(ProofError::MalformedProof);
+    }
+
+    let options = &proof.options;
+    let defaults = default_hashes(hasher, options);
+    let mut hash = match value {
+        Some(value) => hasher::hash_leaf(hasher, value, options),
+        None => defaults[0],
+    };
+
+    let mut steps = proof.steps.iter().peekable();
+    for height in 0..DEPTH {
+        let side = sibling_side(&path, height);
+        let sibling = match steps.next_if(|step| step.level == height + 1) {
+            Some(step) if step.side != side => return Err(ProofError::MalformedProof),
+            Some(step) => step.sibling,
+            None => defaults[height as usize],
+        };
+        hash = match side {
+            Side::Right => hasher::hash_combination(hasher, &hash, &sibling, options),
+            Side::Left => hasher::hash_combination(hasher, &sibling, &hash, options),
+        };
+    }
+
+    // steps out of order or past the root were never used
+    if steps.next().is_some() {
+        return Err(ProofError::MalformedProof);
+    }
+    Ok(hash)
+}
+
+/// Bit of `path` that picks the child at `height` levels above the leaves, most significant first
+fn path_bit(path: &Digest, height: u32) -> bool {
+    let bit = (DEPTH - 1 - height) as usize;
+    path.0[bit / 8] & (0x80 >> (bit % 8)) != 0
+}
+
+/// Which side the sibling of the node `height` levels above the leaves on `path` sits on
+fn sibling_side(path: &Digest, height: u32) -> Side {
+    if path_bit(path, height) { Side::Left } else { Side::Right }
+}
+
+/// `path` with the bits below a node `height` levels above the leaves cleared
+fn prefix(path: &Digest, height: u32) -> Digest {
+    let mut prefix = *path;
+    let cleared_bytes = (height / 8) as usize;
+    for byte in prefix.0.iter_mut().rev().take(cleared_bytes) {
+        *byte = 0;
+    }
+    if cleared_bytes < prefix.0.len() {
+        prefix.0[31 - cleared_bytes] &= 0xff << (height % 8);
+    }
+    prefix
+}
+
+/// The node next to the one at `path`, `height` levels above the leaves
+fn flip_bit(path: &Digest, height: u32) -> Digest {
+    let mut flipped = *path;
+    let bit = (DEPTH - 1 - height) as usize;
+    flipped.0[bit / 8] ^= 0x80 >> (bit % 8);
+    flipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> SparseMerkleTree {
+        let mut tree = SparseMerkleTree::default();
+        for (key, value) in [("alice", "10"), ("bob", "20"), ("carol", "30"), ("dave", "40")] {
+            tree.insert(key.as_bytes(), value.as_bytes());
+        }
+        tree
+    }
+
+    #[test]
+    fn empty_tree_has_the_default_root() {
+        let tree = SparseMerkleTree::default();
+        assert_eq!(tree.root(), tree.default_hash(DEPTH));
+        assert_eq!(tree.root(), default_hashes(&Keccak256, &TreeOptions::default())[DEPTH as usize]);
+    }
+
+    #[test]
+    fn present_and_absent_keys_verify() {
+        let tree = tree();
+        let root = tree.root();
+
+        let proof = tree.prove(b"bob");
+        assert_eq!(verify(&root, b"bob", Some(b"20"), &proof, &Keccak256), Ok(true));
+        assert_eq!(verify(&root, b"bob", None, &proof, &Keccak256), Ok(false));
+
+        let proof = tree.prove(b"eve");
+        assert_eq!(tree.get(b"eve"), None);
+        assert_eq!(verify(&root, b"eve", None, &proof, &Keccak256), Ok(true));
+        assert_eq!(verify(&root, b"eve", Some(b"50"), &proof, &Keccak256), Ok(false));
+    }
+
+    #[test]
+    fn tampered_proofs_are_rejected() {
+        let tree = tree();
+        let root = tree.root();
+        let proof = tree.prove(b"carol");
+        assert!(!proof.steps.is_empty());
+
+        assert_eq!(verify(&root, b"carol", Some(b"31"), &proof, &Keccak256), Ok(false));
+        assert_eq!(verify(&root, b"dave", Some(b"30"), &proof, &Keccak256), Err(ProofError::MalformedProof));
+
+        let mut flipped = proof.clone();
+        flipped.steps[0].side = match flipped.steps[0].side {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        };
+        assert_eq!(verify(&root, b"carol", Some(b"30"), &flipped, &Keccak256), Err(ProofError::MalformedProof));
+
+        // a step moved to another level is either out of place or hashed at the wrong height
+        for level in [0, 1, DEPTH, DEPTH + 1] {
+            let mut moved = proof.clone();
+            let last = moved.steps.len() - 1;
+            if moved.steps[last].level == level {
+                continue;
+            }
+            moved.steps[last].level = level;
+            assert_ne!(verify(&root, b"carol", Some(b"30"), &moved, &Keccak256), Ok(true), "level {}", level);
+        }
+
+        let mut shortened = proof.clone();
+        shortened.steps.pop();
+        assert_eq!(verify(&root, b"carol", Some(b"30"), &shortened, &Keccak256), Ok(false));
+
+        let mut other_root = tree.clone();
+        other_root.insert(b"eve", b"50");
+        assert_eq!(verify(&other_root.root(), b"carol", Some(b"30"), &proof, &Keccak256), Ok(false));
+    }
+
+    #[test]
+    fn insertion_order_does_not_change_the_root() {
+        let mut reversed = SparseMerkleTree::default();
+        for (key, value) in [("dave", "40"), ("carol", "30"), ("bob", "20"), ("alice", "10")] {
+            reversed.insert(key.as_bytes(), value.as_bytes());
+        }
+        assert_eq!(reversed.root(), tree().root());
+    }
+
+    #[test]
+    fn removing_a_key_restores_the_previous_root() {
+        let mut tree = tree();
+        let before = tree.root();
+        tree.insert(b"eve", b"50");
+        assert_ne!(tree.root(), before);
+        assert_eq!(tree.remove(b"eve"), Some(b"50".to_vec()));
+        assert_eq!(tree.root(), before);
+        assert_eq!(tree.remove(b"eve"), None);
+
+        for key in ["alice", "bob", "carol", "dave"] {
+            tree.remove(key.as_bytes());
+        }
+        assert_eq!(tree.root(), tree.default_hash(DEPTH));
+        assert!(tree.nodes.is_empty());
+    }
+}
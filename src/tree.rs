@@ -1,553 +1,876 @@
 use std::collections::HashMap;
 
-use bevy::{asset::Handle, color::Color, math::{Quat, Vec3}, prelude::{Commands, Component, Transform}, sprite::{Sprite, SpriteBundle}, text::{Font, Text, Text2dBundle, TextStyle}};
+use serde::{Deserialize, Serialize};
 
-use crate::hasher;
+use crate::error::MerkleError;
+use crate::hasher::{self, Digest, Keccak256, MerkleHasher, PairEncoding};
+use crate::leaf::Leaf;
+use crate::proof::{self, MultiStep, ProofStep, Side, Verification};
+
+pub use crate::proof::{ConsistencyProof, ExclusionProof, InclusionProof, MultiProof, Neighbour};
 
 #[derive(Clone)]
 #[derive(Debug)]
-#[derive(Component)]
+#[derive(Default)]
 pub struct Node {
-    pub hash: String,
+    pub hash: Digest,
     pub level: u32,
     pub parent_index: (u32, u32),
     pub index: usize,
-    pub position: (f32, f32),
-    pub start_position: (f32, f32), 
-    pub end_position: (f32, f32),  
-    pub is_hash: bool
 }
 
-#[derive(Component)]
-pub struct BranchMarker;
 
-#[derive(Component)]
-pub struct NodeTextMarker{
-    pub node_index: usize,
-    pub node_level: u32
+/// What to do with the last node of a level that has no sibling
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OddNodeRule {
+    /// Hash the node with a copy of itself, as Bitcoin does
+    #[default]
+    Duplicate,
+    /// Carry the node up to the next level unchanged
+    Promote,
+    /// Hash the node with `hasher::EMPTY_HASH`
+    PadEmpty,
 }
 
-
-#[derive(Clone)]
-#[derive(Debug)]
-pub struct InclusionProof {
-    pub proof: Vec<HashMap<u32, String>>,
-    pub is_valid: bool,
-    pub proof_link: Vec<HashMap<u32, u32>>
+/// Order the leaves are placed in when a tree is built
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeafOrder {
+    /// The order the words were given in
+    #[default]
+    AsGiven,
+    /// Ascending by leaf hash
+    ByHash,
+    /// Ascending by the encoded leaf
+    ByValue,
 }
 
-impl Default for InclusionProof {
-    fn default() -> Self {
-        InclusionProof{
-            proof: Vec::new(),
-            is_valid: false,
-            proof_link: Vec::new()
+impl LeafOrder {
+    pub const ALL: [LeafOrder; 3] = [LeafOrder::AsGiven, LeafOrder::ByValue, LeafOrder::ByHash];
+
+    /// The order after this one in `ALL`, wrapping around
+    pub fn next(self) -> LeafOrder {
+        let position = LeafOrder::ALL.iter().position(|order| *order == self).unwrap_or(0);
+        LeafOrder::ALL[(position + 1) % LeafOrder::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LeafOrder::AsGiven => "As given",
+            LeafOrder::ByHash => "By hash",
+            LeafOrder::ByValue => "By value",
         }
     }
+
+    pub fn is_sorted(&self) -> bool {
+        *self != LeafOrder::AsGiven
+    }
+
+    /// What leaves are compared by: the hash for `ByHash`, the encoded leaf otherwise
+    pub fn sort_key<'a>(&self, value: &'a [u8], leaf: &'a Digest) -> &'a [u8] {
+        match self {
+            LeafOrder::ByHash => leaf.as_bytes(),
+            LeafOrder::AsGiven | LeafOrder::ByValue => value,
+        }
+    }
+}
+
+/// How `MerkleTree::remove` takes a leaf out of the tree
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Default)]
+pub enum RemovePolicy {
+    /// Keep the leaf's place with `hasher::EMPTY_HASH` as its hash and an empty value and label,
+    /// so the other leaves keep their indices
+    #[default]
+    Tombstone,
+    /// Drop the leaf, shift the following ones down and rebuild the tree
+    Compact,
+}
+
+/// Settings a tree was built with. Proofs carry a copy so they can be checked the same way.
+#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TreeOptions {
+    pub odd_rule: OddNodeRule,
+    pub encoding: PairEncoding,
+    /// Prefix leaves with `hasher::LEAF_PREFIX` and internal nodes with `hasher::NODE_PREFIX` (RFC 6962),
+    /// so an internal node can't be passed off as a leaf
+    pub domain_separation: bool,
+    /// Sorted leaves allow exclusion proofs
+    pub leaf_order: LeafOrder,
+    /// Hash the two children of a node in ascending order, as on-chain verifiers such as OpenZeppelin's
+    /// `MerkleProof` do, so a proof checks without knowing which side each sibling is on
+    pub sorted_pairs: bool,
 }
 
+
+#[derive(Clone)]
 #[derive(Debug)]
+#[derive(Default)]
 pub struct WordToProve {
     pub word: String,
     pub index: usize,
-    pub hash: String,
+    pub hash: Digest,
     pub display_hash: String
 }
 
-impl Default for WordToProve {
-    fn default() -> Self {
-        WordToProve{
-            word: "".to_string(),
-            index: 0,
-            hash: "".to_string(),
-            display_hash: "".to_string()
-        }
-    }
-}
-
 
+#[derive(Clone)]
 #[derive(Debug)]
-pub struct MerkleTree{
+#[derive(Default)]
+pub struct MerkleTree<H: MerkleHasher = Keccak256>{
     pub root: Option<Node>,
     pub nodes: HashMap<u32,Vec<Node>>,
+    /// Labels of the leaves, only used to draw them
     pub words: Vec<String>,
+    /// Encoded leaves, what `LeafOrder::ByValue` sorts by
+    pub values: Vec<Vec<u8>>,
     pub levels: u32,
-    pub size: (f32, f32),
+    pub options: TreeOptions,
+    pub hasher: H,
     pub proof: Option<InclusionProof>,
+    pub multi_proof: Option<MultiProof>,
+    pub exclusion_proof: Option<ExclusionProof>,
     pub word_to_prove: Option<WordToProve>
 }
 
 impl MerkleTree {
-    fn new(hashes: &Vec<String>, words: Vec<String>) -> MerkleTree {
+    pub fn format_hash(hash: &str, first_len: usize, last_len: usize, dots: &str) -> String {
+        if hash.len() <= first_len + last_len {
+            return hash.to_string();
+        }
+        format!(
+            "{}{}{}",
+            &hash[0..first_len],
+            dots,               
+            &hash[hash.len() - last_len..]
+        )
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    fn new(hashes: &[Digest], values: Vec<Vec<u8>>, words: Vec<String>, hasher: H, options: TreeOptions) -> MerkleTree<H> {
         let mut nodes = Vec::new();
         let mut nodes_map = HashMap::new();
         for (i, hash) in hashes.iter().enumerate() {
             let node = Node{
-                hash: hash.to_string(),
+                hash: *hash,
                 level: 1,
                 parent_index: (0, 0),
                 index: i,
-                ..Default::default()
             };
             nodes.push(node);
         }
-        // Per my implementation, minimum words is 4 and max is 16, hence the levels will be 4 and 5 respectively
-        // So we can calculate the levels by taking the log base 2 of the number of words
-        println!("Words and Nodes");
-        println!("{:?}", words);
-        println!("{:?}", nodes);
-        let level = ((words.len() as f64).log2()) as u32 +1;
-        nodes_map.insert(1, nodes.clone());
-        return MerkleTree{
+        // levels is set once the tree is built, an empty word list has none
+        let level = if nodes.is_empty() { 0 } else { 1 };
+        nodes_map.insert(1, nodes);
+        MerkleTree{
             root: None,
             nodes: nodes_map,
-            words: words.clone(),
+            words,
+            values,
             levels: level,
-            size: (150.0, 50.0),
+            options,
+            hasher,
             proof: None,
+            multi_proof: None,
+            exclusion_proof: None,
             word_to_prove: None
-        };
-        // instead of clone what can we do?
+        }
     }
 
     
     /// Build the Merkle Tree
-    /// combinations for each sub level
+    /// combinations for each sub level, until a level holds a single node
     fn build(&mut self) {
+        let mut current_level = 1;
 
+        while let Some(nodes) = self.nodes.get(&current_level) {
+            if nodes.len() <= 1 {
+                break;
+            }
 
-        for current_level in 0..self.levels {
-            if let Some(nodes) = self.nodes.get(&current_level).cloned() {
-                let mut new_nodes = Vec::new();
-        
-                for index in (0..nodes.len()).step_by(2) {
-                    
-                    if index == nodes.len() - 1 {
-                        continue;
-                    }
-        
-                    let left = &nodes[index];
-                    let right = &nodes[index + 1];
-        
-                    let hash = hasher::hash_combination(&left.hash, &right.hash);
-        
-                    let new_node = Node {
-                        hash,
-                        level: current_level + 1,
-                        parent_index: (left.index as u32, right.index as u32),
-                        index: new_nodes.len(), // Index in the new level
-                        ..Default::default()
-                    };
-        
-                    new_nodes.push(new_node.clone());
-        
-                    if current_level + 1 == self.levels {
-                        self.root = Some(new_node);
-                    }
-                }
-        
-                self.nodes.insert(current_level + 1, new_nodes.clone());
+            let mut new_nodes = Vec::new();
+
+            for index in (0..nodes.len()).step_by(2) {
+                new_nodes.push(self.parent_node(&nodes[index], nodes.get(index + 1)));
             }
+
+            self.nodes.insert(current_level + 1, new_nodes);
+            current_level += 1;
         }
+
+        self.levels = current_level;
+        self.root = self.nodes.get(&current_level).and_then(|nodes| nodes.first().cloned());
     }
 
-    fn get_length_and_degree(current_depth: u32, max_depth: u32) -> (f32, f32) {
-        // Handle up to max 5 levels
-        match (max_depth, current_depth) {
-            (3, 4) => (620.0, 85.0),
-            (3, 3) => (300.0, 75.0),
-            (3, 2) => (150.0, 60.0),
-            (3, 1) => (60.0, 35.0),
-            (3, 0) => (80.0, 30.0),
-            (4, 5) => (450.0, 80.0),
-            (4, 4) => (400.0, 85.0),
-            (4, 3) => (180.0, 85.0),
-            (4, 2) => (95.0, 75.0),
-            (4, 1) => (40.0, 40.0),
-            (4, 0) => (20.0, 10.0),
-            _ => (0.0, 0.0),
+    /// The node one level above `left` and `right`, with `right` missing for the odd node out
+    fn parent_node(&self, left: &Node, right: Option<&Node>) -> Node {
+        let (hash, parent_index) = if let Some(right) = right {
+            (hasher::hash_combination(&self.hasher, &left.hash, &right.hash, &self.options), (left.index as u32, right.index as u32))
+        } else {
+            // odd node out, handled per the configured rule
+            let hash = match self.options.odd_rule {
+                OddNodeRule::Duplicate => hasher::hash_combination(&self.hasher, &left.hash, &left.hash, &self.options),
+                OddNodeRule::Promote => left.hash,
+                OddNodeRule::PadEmpty => hasher::hash_combination(&self.hasher, &left.hash, &hasher::EMPTY_HASH, &self.options),
+            };
+            (hash, (left.index as u32, left.index as u32))
+        };
+
+        Node {
+            hash,
+            level: left.level + 1,
+            parent_index,
+            index: left.index / 2, // Index in the new level
         }
     }
 
-    fn calculate_endpoints_tuple(start_point: &(f32, f32), length: f32, angle: f32) -> ((f32, f32), (f32, f32)) {
-        let angle_radians = angle.to_radians();
-        let symangle_radians = (360.0 - angle).to_radians();
-    
-        let end_point_zero = (
-            start_point.0 - length * angle_radians.sin(),
-            start_point.1 + length * angle_radians.cos(),
-        );
-    
-        let end_point_one = (
-            start_point.0 - length * symangle_radians.sin(),
-            start_point.1 + length * symangle_radians.cos(),
-        );
-    
-        (end_point_zero, end_point_one)
+    /// Append a leaf, recomputing only its path to the root.
+    /// Returns the index of the new leaf.
+    pub fn push<L: Leaf + ?Sized>(&mut self, leaf: &L) -> Result<usize, MerkleError> {
+        let value = leaf.encode();
+        let hash = hasher::hash_leaf(&self.hasher, &value, &self.options);
+        let leaves = self.nodes.entry(1).or_default();
+        let index = leaves.len();
+        leaves.push(Node {
+            hash,
+            level: 1,
+            parent_index: (0, 0),
+            index,
+        });
+        self.words.push(leaf.label());
+        self.values.push(value.into_owned());
+        self.rehash_path(index)?;
+        Ok(index)
     }
-    
 
-    pub fn graph(&mut self, screen_height: f32, mut commands: Commands,handle: &Handle<Font>) {
-        let root_point = (0.0, -(screen_height / 2.0) + 50.0); // Origin is at the center
+    /// Replace the leaf at `index`, recomputing only its path to the root
+    pub fn update<L: Leaf + ?Sized>(&mut self, index: usize, leaf: &L) -> Result<(), MerkleError> {
+        self.leaf(index)?;
+        let value = leaf.encode();
+        let hash = hasher::hash_leaf(&self.hasher, &value, &self.options);
+        if let Some(node) = self.nodes.get_mut(&1).and_then(|leaves| leaves.get_mut(index)) {
+            node.hash = hash;
+        }
+        if let Some(word) = self.words.get_mut(index) {
+            *word = leaf.label();
+        }
+        if let Some(stored) = self.values.get_mut(index) {
+            *stored = value.into_owned();
+        }
+        self.rehash_path(index)
+    }
 
-        println!("Root Point: {:?}", root_point);
-    
-        for current_level in (0..self.levels + 1).rev() {
-            if current_level == 0 {
-                break;
+    /// Take the leaf at `index` out of the tree. Proofs made before are dropped from the tree and
+    /// show up as stale when checked with `validate_proof`. The last leaf can't be removed.
+    pub fn remove(&mut self, index: usize, policy: RemovePolicy) -> Result<(), MerkleError> {
+        self.leaf(index)?;
+        match policy {
+            RemovePolicy::Tombstone => {
+                if let Some(node) = self.nodes.get_mut(&1).and_then(|leaves| leaves.get_mut(index)) {
+                    node.hash = hasher::EMPTY_HASH;
+                }
+                if let Some(word) = self.words.get_mut(index) {
+                    word.clear();
+                }
+                if let Some(value) = self.values.get_mut(index) {
+                    value.clear();
+                }
+                self.rehash_path(index)
             }
-            println!("Current Level: {:?}", current_level);
-    
-            let (length, angle) = MerkleTree::get_length_and_degree(current_level, self.levels);
-    
-
-            let mut calculated_start_positions: HashMap<u32,(f32,f32)> = HashMap::new();
-    
-            if let Some(nodes) = self.nodes.get_mut(&current_level) {
-                for (_, node) in nodes.iter_mut().enumerate() {
-                    let mut parent_position = if current_level == self.levels {
-                        root_point 
-                    }  else {
-                        node.start_position
-                    };
-
-                    parent_position.1 = parent_position.1 + self.size.1 / 2.0;
-
-                    println!("Node: {:?}", node);
-                    println!("Length: {:?}", length);
-                    println!("Angle: {:?}", angle);
-                    println!("Node level: {:?} and end position {:?}", node.level, node.end_position);
-
-    
-                    // Store the parent node position
-                    node.position = parent_position;
-    
-                    // Calculate the end points for the current node's children
-                    let (end_point_zero, end_point_one) = MerkleTree::calculate_endpoints_tuple(&parent_position, length, angle);
-    
-                    calculated_start_positions.insert(node.parent_index.0, (end_point_zero.0, end_point_zero.1 + self.size.1/2.0));
-                    calculated_start_positions.insert(node.parent_index.1, (end_point_one.0, end_point_one.1 + self.size.1/2.0));
-
-                    // Store these end points to be used in the next iteration
-                    node.start_position = parent_position;
-                    node.position = (parent_position.0, parent_position.1 - self.size.1 / 2.0);
-    
-                    // Draw the lines to the child nodes
-                    // draw node, then draw lines, lines starting point should be the mid point of the node
-                    Self::draw_node(&mut commands, node,self.size, handle,true);
-
-                    if current_level ==1 {
-                        continue;
-                    }
-
-                    Self::draw_line(&mut commands, parent_position, end_point_zero, 1.0);
-                    Self::draw_line(&mut commands, parent_position, end_point_one, 1.0);
+            RemovePolicy::Compact => {
+                if self.leaf_count() == 1 {
+                    return Err(MerkleError::EmptyTree);
+                }
+                let mut leaves = self.nodes.remove(&1).unwrap_or_default();
+                leaves.remove(index);
+                for (i, leaf) in leaves.iter_mut().enumerate() {
+                    leaf.index = i;
+                }
+                if index < self.words.len() {
+                    self.words.remove(index);
+                }
+                if index < self.values.len() {
+                    self.values.remove(index);
                 }
 
+                // every level above the leaves is redone
+                self.nodes.clear();
+                self.nodes.insert(1, leaves);
+                self.build();
+                self.proof = None;
+                self.multi_proof = None;
+                self.exclusion_proof = None;
+                self.word_to_prove = None;
+                Ok(())
             }
+        }
+    }
 
-            if current_level == 1 {
+    /// Level and index of every node from the leaf at `leaf_index` up to the root
+    pub fn path_to_root(&self, leaf_index: usize) -> Vec<(u32, usize)> {
+        (1..=self.levels).map(|level| (level, leaf_index >> (level - 1))).collect()
+    }
+
+    /// Recompute the parents of the leaf at `leaf_index` up to the root, adding a level when the
+    /// tree outgrows its root. Stored proofs no longer match and are dropped.
+    fn rehash_path(&mut self, leaf_index: usize) -> Result<(), MerkleError> {
+        let mut level = 1;
+        let mut node_index = leaf_index;
+
+        loop {
+            let level_nodes = self.level_nodes(level)?;
+            if level_nodes.len() <= 1 {
                 break;
             }
-                // update the start positions of the nodes in the next level with the end positions of the current level
-                if let Some(parent_nodes) = self.nodes.get_mut(&(current_level - 1)) {
-                    for node in parent_nodes.iter_mut() {
-                        node.start_position = calculated_start_positions.get(&(node.index as u32)).unwrap().clone();
-                    }
-                }
-
-        }
+            let left_index = node_index & !1;
+            let parent = self.parent_node(&level_nodes[left_index], level_nodes.get(left_index + 1));
 
-        // draw words
-        if let Some(prime_nodes) = self.nodes.get_mut(&1) {
-            for (i, node) in prime_nodes.iter_mut().enumerate() {
-
-                // Draw a straight short line from node to + 30 on y axis
-                let start_point = (node.position.0, node.position.1 + self.size.1 / 2.0);
-                let size_y = self.size.1;
-                let end_point = (start_point.0, start_point.1 + 30.0 + size_y /2.0);
-                Self::draw_line(&mut commands, start_point, end_point, 1.0);
-                let word_node = Node{
-                    hash: self.words[i].clone(),
-                    level: 0,
-                    parent_index: (0, 0),
-                    index: i,
-                    position: (start_point.0, start_point.1 + size_y + 30.0),
-                    start_position: (start_point.0, start_point.1 + size_y + 30.0),
-                    end_position: (start_point.0, start_point.1 + size_y + 30.0),
-                    is_hash: false
-                };
-                // Then draw a node with the word in it
-                Self::draw_node(&mut commands, &word_node, self.size, handle,false);
+            let parents = self.nodes.entry(level + 1).or_default();
+            match parents.get_mut(parent.index) {
+                Some(node) => *node = parent,
+                None => parents.push(parent),
             }
+            node_index /= 2;
+            level += 1;
         }
 
-        //println!("Nodes: {:?}", self.nodes);
+        self.levels = level;
+        self.root = self.nodes.get(&level).and_then(|nodes| nodes.first().cloned());
+        self.proof = None;
+        self.multi_proof = None;
+        self.exclusion_proof = None;
+        Ok(())
     }
 
-    pub fn format_hash(hash: &str, first_len: usize, last_len: usize, dots: &str) -> String {
-        if hash.len() <= first_len + last_len {
-            return hash.to_string();
-        }
-        format!(
-            "{}{}{}",
-            &hash[0..first_len],
-            dots,               
-            &hash[hash.len() - last_len..]
-        )
+    /// Hash of the root node, if the tree has been built
+    pub fn root_hash(&self) -> Option<Digest> {
+        self.root.as_ref().map(|root| root.hash)
     }
-    
-    
 
-    fn draw_line(commands: &mut Commands, point1: (f32, f32), point5: (f32, f32), thickness: f32) {
-        
-        let mid_point = (
-            (point1.0 + point5.0) / 2.0,
-            (point1.1 + point5.1) / 2.0,
-        );
-    
-        // Calculate the angle of rotation
-        let angle = (point5.1 - point1.1).atan2(point5.0 - point1.0);
-    
-        // Calculate the distance between the two points
-        let length = ((point5.0 - point1.0).powi(2) + (point5.1 - point1.1).powi(2)).sqrt();
-    
-        // Spawn the line (as a scaled and rotated sprite)
-        commands.spawn(SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(mid_point.0, mid_point.1, 0.0),
-                rotation: Quat::from_rotation_z(angle),           
-                scale: Vec3::new(length, thickness, 1.0),
-                ..Default::default()
-            },
-            sprite: Sprite {
-                color: Color::BLACK,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(BranchMarker);
+    /// Number of leaves
+    pub fn leaf_count(&self) -> usize {
+        self.nodes.get(&1).map_or(0, |leaves| leaves.len())
+    }
 
-        println!("Drawing Line from {:?} to {:?}", point1, point5);
+    fn level_nodes(&self, level: u32) -> Result<&Vec<Node>, MerkleError> {
+        self.nodes.get(&level).ok_or(MerkleError::MissingLevel(level))
     }
 
-    fn draw_node(commands: &mut Commands, node: &Node, size: (f32, f32),handle: &Handle<Font>, is_hash: bool) {
-        let (x, y) = node.position;
-        let (size_x,size_y) = size;
+    fn leaf(&self, word_index: usize) -> Result<&Node, MerkleError> {
+        let leaves = self.level_nodes(1)?;
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+        leaves.get(word_index).ok_or(MerkleError::IndexOutOfRange { index: word_index, size: leaves.len() })
+    }
 
-        let display_text = if is_hash {
-            Self::format_hash(&node.hash, 6, 6, "...")
-        } else {
-            node.hash.clone()
-        };
+    /// Generate the proof for `word_index`, check it and keep it as `self.proof`
+    pub fn inclusion_proof(&mut self, word_index: usize) -> Result<InclusionProof, MerkleError> {
+        let proof = self.make_inclusion_proof(word_index)?;
+        self.proof = Some(proof);
 
-        commands.spawn(SpriteBundle {
-            sprite: Sprite {
-                color: Color::BLACK,
-                ..Default::default()
-            },
-            //color: Color::srgb(0.5, 0.5, 1.0)
-            transform: Transform {
-                translation: Vec3::new(x, y, 0.0),
-                scale: Vec3::new(size_x,size_y, 1.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(node.clone());
-
-         // Adjust text size based on content length
-        let calculated_font_size = (size_y * 0.5).min(size_x / (display_text.len() as f32 * 0.5));
-
-        // Draw the text
-        commands.spawn(Text2dBundle {
-            text: Text::from_section(display_text, 
-            TextStyle { 
-                font: handle.clone(),
-                 font_size: calculated_font_size, color: Color::WHITE }),
-            transform: Transform::from_translation(Vec3::new(x, y, 10.0)),
-            ..Default::default()
-        })
-        .insert(NodeTextMarker{node_index: node.index, node_level: node.level}); 
+        let outcome = self.check_inclusion_proof(word_index);
+        let proof = self.proof.as_mut().unwrap();
+        proof.is_valid = outcome.is_valid();
+        proof.outcome = Some(outcome);
+        Ok(proof.clone())
     }
 
-
-    pub fn inclusion_proof(&mut self, word_index: usize) {
-        let word_node = self.nodes.get(&1).unwrap().get(word_index).unwrap();
+    /// The unchecked proof for `word_index`
+    fn make_inclusion_proof(&self, word_index: usize) -> Result<InclusionProof, MerkleError> {
+        let word_node = self.leaf(word_index)?;
     
-        let mut proof = InclusionProof::default();
-        let mut current_node = word_node.clone();
-        
-        println!("Inclusion Proof for word index: {:?}", word_index);
-        println!("Current Node: {:?}", current_node);
+        let mut proof = InclusionProof{
+            leaf_index: word_index,
+            tree_size: self.level_nodes(1)?.len(),
+            root: self.root_hash().ok_or(MerkleError::EmptyTree)?,
+            options: self.options,
+            ..Default::default()
+        };
+        let mut current_node = word_node;
     
         for level in 1..self.levels {
-            let sibling_index = if level == 1 {
-                let sibling_index = if current_node.index % 2 == 0 {
-                    current_node.index + 1
-                } else {
-                    current_node.index - 1
-                };
-    
-                let child_index = current_node.index / 2;
-                println!("Level 1: Moving up to child node at index {} in level 2", child_index);
-    
-                current_node = self.nodes.get(&(level + 1)).unwrap().get(child_index).unwrap().clone();
-    
-                sibling_index
+            let (sibling_index, side) = if current_node.index.is_multiple_of(2) {
+                (current_node.index + 1, Side::Right)
             } else {
-                let child_index = current_node.index / 2;
-    
-                println!("Level: {}, Child Index: {}", level, child_index);
-    
-                let sibling_index = if current_node.index % 2 == 0 {
-                    current_node.index + 1
-                } else {
-                    current_node.index - 1
-                };
-    
-                current_node = self.nodes.get(&(level + 1)).unwrap().get(child_index).unwrap().clone();
-    
-                sibling_index
+                (current_node.index - 1, Side::Left)
             };
+
+            let level_nodes = self.level_nodes(level)?;
+
+            // a missing sibling means current_node is the odd one out at this level
+            let sibling_hash = match level_nodes.get(sibling_index) {
+                Some(sibling_node) => Some(sibling_node.hash),
+                None => match self.options.odd_rule {
+                    OddNodeRule::Duplicate => Some(current_node.hash),
+                    OddNodeRule::Promote => None,
+                    OddNodeRule::PadEmpty => Some(hasher::EMPTY_HASH),
+                },
+            };
+
+            if let Some(sibling) = sibling_hash {
+                proof.steps.push(ProofStep { sibling, side, level });
+            }
+
+            let child_index = current_node.index / 2;
+            current_node = self.level_nodes(level + 1)?.get(child_index).ok_or(MerkleError::MissingLevel(level + 1))?;
+        }
     
-            println!("Level: {:?}, Sibling Index: {:?}", level, sibling_index);
-    
-            if let Some(sibling_node) = self.nodes.get(&level).and_then(|level_nodes| level_nodes.get(sibling_index as usize)) {
-                println!("Sibling Node: {:?}", sibling_node);
-    
-                let mut proof_map = HashMap::new();
-                let mut proof_link_map = HashMap::new();
-                proof_map.insert(sibling_node.index as u32, sibling_node.hash.clone());
-                proof_link_map.insert(sibling_node.level, sibling_node.index as u32);
+        Ok(proof)
+    }
 
-                proof.proof.push(proof_map);
-                proof.proof_link.push(proof_link_map);
-            } else {
-                println!("Error: Sibling node at level {} with index {} does not exist!", level, sibling_index);
-                return;
+
+    /// Check the stored proof for `word_index` against this tree's root, using the
+    /// word waiting to be proven in its place if there is one
+    pub fn validate_inclusion_proof(&self, word_index: usize) -> Result<(), MerkleError> {
+        let word_node = self.leaf(word_index)?;
+        let mut hash = word_node.hash;
+        if let Some(word_to_prove) = &self.word_to_prove {
+            if word_to_prove.index == word_index {
+                hash = word_to_prove.hash;
             }
         }
     
-        self.proof = Some(proof);
-        println!("Generated Proof: {:?}", self.proof);
+        // Get the stored proof
+        let proof = self.proof.as_ref().ok_or(MerkleError::MalformedProof)?;
+        let root_hash = self.root_hash().ok_or(MerkleError::EmptyTree)?;
+        if proof.root != root_hash {
+            return Err(MerkleError::StaleProof { proof_root: proof.root, root: root_hash });
+        }
 
-        let valid = self.validate_inclusion_proof(word_index);
+        let computed = proof::compute_root(&hash, word_index, proof, &self.hasher)?;
+        if computed != root_hash {
+            return Err(MerkleError::HashMismatch { expected: root_hash, found: computed });
+        }
+        Ok(())
+    }
 
-        if valid {
-            println!("Proof is valid!");
-        } else {
-            println!("Proof is invalid!");
+    /// Check a proof handed out earlier against the tree as it is now.
+    /// A proof made before the tree changed gives `MerkleError::StaleProof`.
+    pub fn validate_proof(&self, proof: &InclusionProof) -> Result<(), MerkleError> {
+        let root_hash = self.root_hash().ok_or(MerkleError::EmptyTree)?;
+        if proof.root != root_hash {
+            return Err(MerkleError::StaleProof { proof_root: proof.root, root: root_hash });
         }
 
+        let leaf = self.leaf(proof.leaf_index)?;
+        let computed = proof::compute_root(&leaf.hash, proof.leaf_index, proof, &self.hasher)?;
+        if computed != root_hash {
+            return Err(MerkleError::HashMismatch { expected: root_hash, found: computed });
+        }
+        Ok(())
     }
 
+    /// `validate_inclusion_proof` folded into a `Verification` outcome
+    pub fn check_inclusion_proof(&self, word_index: usize) -> Verification {
+        match self.validate_inclusion_proof(word_index) {
+            Ok(()) => Verification::Valid,
+            Err(error) => Verification::from(&error),
+        }
+    }
 
-    pub fn validate_inclusion_proof(&self, word_index: usize) -> bool {
-        let word_node = self.nodes.get(&1).unwrap().get(word_index).unwrap();
-        let mut hash = word_node.hash.clone();
-        if self.word_to_prove.is_some() {
-            let word_to_prove = self.word_to_prove.as_ref().unwrap();
-            if word_to_prove.index == word_index {
-                hash = word_to_prove.hash.clone();
+    /// Generate one proof for all of `word_indices`, check it and keep it as `self.multi_proof`.
+    /// The indices may come in any order, duplicates are dropped.
+    pub fn multi_proof(&mut self, word_indices: &[usize]) -> Result<MultiProof, MerkleError> {
+        let mut leaf_indices = word_indices.to_vec();
+        leaf_indices.sort_unstable();
+        leaf_indices.dedup();
+        if leaf_indices.is_empty() {
+            return Err(MerkleError::MalformedProof);
+        }
+        for &index in &leaf_indices {
+            self.leaf(index)?;
+        }
+
+        let mut proof = MultiProof{
+            tree_size: self.level_nodes(1)?.len(),
+            root: self.root_hash().ok_or(MerkleError::EmptyTree)?,
+            options: self.options,
+            ..Default::default()
+        };
+
+        for step in proof::multi_path_shape(&leaf_indices, proof.tree_size, self.options.odd_rule) {
+            match step {
+                MultiStep::Pair { .. } => proof.proof_flags.push(true),
+                MultiStep::Sibling { level, index, .. } => {
+                    let level_nodes = self.level_nodes(level)?;
+                    let node = level_nodes.get(index).ok_or(MerkleError::MissingLevel(level))?;
+                    let sibling = match level_nodes.get(index ^ 1) {
+                        Some(sibling_node) => sibling_node.hash,
+                        // odd one out, Promote never gets here
+                        None if self.options.odd_rule == OddNodeRule::PadEmpty => hasher::EMPTY_HASH,
+                        None => node.hash,
+                    };
+                    proof.proof.push(sibling);
+                    proof.proof_flags.push(false);
+                }
+                MultiStep::Promote { .. } => {}
             }
         }
-    
-        // Get the stored proof
-        if let Some(proof) = &self.proof {
-            let mut current_hash = hash;
-            let mut current_node_index = word_node.index;
-            println!("Current node index: {:?}", current_node_index);
-    
-            // Traverse through the proof and recompute the hash up to the root
-            for level in 1..self.levels {
-                let proof_map = proof.proof.get((level - 1) as usize).unwrap();
-                let sibling_hash = proof_map.values().next().unwrap();
-    
-                let proof_link_map = proof.proof_link.get((level - 1) as usize).unwrap();
-                let sibling_index = *proof_link_map.get(&level).unwrap() as usize;
-    
-                current_node_index = if sibling_index % 2 == 0 {
-                    sibling_index + 1
-                } else {
-                    sibling_index - 1
-                };
-    
-                // Combine hashes in the correct order
-                if current_node_index % 2 == 0 {
-                    current_hash = hasher::hash_combination(&current_hash, sibling_hash);
-                } else {
-                    current_hash = hasher::hash_combination(sibling_hash, &current_hash);
+        proof.leaf_indices = leaf_indices;
+
+        self.multi_proof = Some(proof);
+
+        let outcome = self.check_multi_proof();
+        let proof = self.multi_proof.as_mut().unwrap();
+        proof.is_valid = outcome.is_valid();
+        proof.outcome = Some(outcome);
+        Ok(proof.clone())
+    }
+
+    /// Prove that `leaf` is not in the tree: give the proofs of the two leaves it would sit between,
+    /// check them and keep the result as `self.exclusion_proof`. Needs a tree built with a
+    /// sorted `LeafOrder`.
+    pub fn exclusion_proof<L: Leaf + ?Sized>(&mut self, leaf: &L) -> Result<ExclusionProof, MerkleError> {
+        let order = self.options.leaf_order;
+        if !order.is_sorted() {
+            return Err(MerkleError::Unsorted);
+        }
+
+        let leaves = self.level_nodes(1)?;
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+        let keys: Vec<&[u8]> = leaves.iter().zip(self.values.iter())
+            .map(|(leaf, value)| order.sort_key(value, &leaf.hash))
+            .collect();
+        // pushes and updates don't keep the order
+        if keys.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(MerkleError::Unsorted);
+        }
+
+        let value = leaf.encode();
+        let hash = hasher::hash_leaf(&self.hasher, &value, &self.options);
+        let key = order.sort_key(&value, &hash);
+        let position = keys.partition_point(|leaf_key| *leaf_key < key);
+        if keys.get(position) == Some(&key) {
+            return Err(MerkleError::WordIncluded { index: position });
+        }
+
+        let neighbour = |index: usize| -> Result<Neighbour, MerkleError> {
+            Ok(Neighbour {
+                word: self.words.get(index).cloned().unwrap_or_default(),
+                value: self.values.get(index).cloned().unwrap_or_default(),
+                proof: self.make_inclusion_proof(index)?,
+            })
+        };
+        let proof = ExclusionProof {
+            left: if position > 0 { Some(neighbour(position - 1)?) } else { None },
+            right: if position < keys.len() { Some(neighbour(position)?) } else { None },
+            ..Default::default()
+        };
+        self.exclusion_proof = Some(proof);
+
+        let outcome = self.check_exclusion_proof(&*value);
+        let proof = self.exclusion_proof.as_mut().unwrap();
+        proof.is_valid = outcome.is_valid();
+        proof.outcome = Some(outcome);
+        Ok(proof.clone())
+    }
+
+    /// Check the stored exclusion proof for `leaf` against this tree's root
+    pub fn validate_exclusion_proof<L: Leaf + ?Sized>(&self, leaf: &L) -> Result<(), MerkleError> {
+        let proof = self.exclusion_proof.as_ref().ok_or(MerkleError::MalformedProof)?;
+        let root_hash = self.root_hash().ok_or(MerkleError::EmptyTree)?;
+        for neighbour in [&proof.left, &proof.right].into_iter().flatten() {
+            if neighbour.proof.root != root_hash {
+                return Err(MerkleError::StaleProof { proof_root: neighbour.proof.root, root: root_hash });
+            }
+            let neighbour_leaf = hasher::hash_leaf(&self.hasher, &neighbour.value, &neighbour.proof.options);
+            let computed = proof::compute_root(&neighbour_leaf, neighbour.proof.leaf_index, &neighbour.proof, &self.hasher)?;
+            if computed != root_hash {
+                return Err(MerkleError::HashMismatch { expected: root_hash, found: computed });
+            }
+        }
+
+        // the neighbours are proven, so a failure here means they don't bracket the leaf
+        if !proof::verify_exclusion(&root_hash, &leaf.encode(), proof, &self.hasher)? {
+            return Err(MerkleError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    /// `validate_exclusion_proof` folded into a `Verification` outcome
+    pub fn check_exclusion_proof<L: Leaf + ?Sized>(&self, leaf: &L) -> Verification {
+        match self.validate_exclusion_proof(leaf) {
+            Ok(()) => Verification::Valid,
+            Err(error) => Verification::from(&error),
+        }
+    }
+
+    /// Check the stored multiproof against this tree's root, using the word waiting
+    /// to be proven in its place if there is one
+    pub fn validate_multi_proof(&self) -> Result<(), MerkleError> {
+        let proof = self.multi_proof.as_ref().ok_or(MerkleError::MalformedProof)?;
+        let mut leaves = Vec::new();
+        for &index in &proof.leaf_indices {
+            let mut hash = self.leaf(index)?.hash;
+            if let Some(word_to_prove) = &self.word_to_prove {
+                if word_to_prove.index == index {
+                    hash = word_to_prove.hash;
                 }
-                // println!("Sibling Hash: {:?}", sibling_hash);
-                // println!("Computed Hash at Level {}: {:?}", level, current_hash);
             }
-    
-            // Compare the computed hash with the root hash of the tree
-            let root_hash = self.nodes.get(&self.levels).unwrap().get(0).unwrap().hash.clone();
-            // println!("Computed Root Hash: {:?}", current_hash);
-            // println!("Actual Root Hash: {:?}", root_hash);
-    
-            return current_hash == root_hash;
+            leaves.push(hash);
         }
-    
-        println!("No proof found!");
-        false
+        let root_hash = self.root_hash().ok_or(MerkleError::EmptyTree)?;
+        if proof.root != root_hash {
+            return Err(MerkleError::StaleProof { proof_root: proof.root, root: root_hash });
+        }
+
+        let computed = proof::compute_multi_root(&leaves, proof, &self.hasher)?;
+        if computed != root_hash {
+            return Err(MerkleError::HashMismatch { expected: root_hash, found: computed });
+        }
+        Ok(())
     }
 
-    
-    
-}
+    /// `validate_multi_proof` folded into a `Verification` outcome
+    pub fn check_multi_proof(&self) -> Verification {
+        match self.validate_multi_proof() {
+            Ok(()) => Verification::Valid,
+            Err(error) => Verification::from(&error),
+        }
+    }
 
-impl Default for MerkleTree {
-    fn default() -> Self {
-        MerkleTree{
-            root: None,
-            nodes: HashMap::new(),
-            words: Vec::new(),
-            levels: 3,
-            size: (0.0,0.0),
-            proof: None,
-            word_to_prove: None
+    /// Prove that the first `old_size` leaves of this tree, built on their own with the same
+    /// options, give a tree this one extends
+    pub fn consistency_proof(&self, old_size: usize) -> Result<ConsistencyProof, MerkleError> {
+        let new_size = self.leaf_count();
+        if old_size == 0 {
+            return Err(MerkleError::EmptyTree);
+        }
+        if old_size > new_size {
+            return Err(MerkleError::IndexOutOfRange { index: old_size, size: new_size });
         }
+
+        let mut proof = ConsistencyProof{
+            old_size,
+            new_size,
+            options: self.options,
+            ..Default::default()
+        };
+        if old_size == new_size {
+            return Ok(proof);
+        }
+
+        let (start_level, mut node_index, is_old_root) = proof::consistency_start(old_size);
+        if !is_old_root {
+            let start = self.level_nodes(start_level)?.get(node_index).ok_or(MerkleError::MissingLevel(start_level))?;
+            proof.hashes.push(start.hash);
+        }
+
+        for level in start_level..self.levels {
+            let level_nodes = self.level_nodes(level)?;
+            let sibling = if node_index.is_multiple_of(2) {
+                // only nodes past the old tree are on the right, missing ones are made up by the verifier
+                level_nodes.get(node_index + 1)
+            } else {
+                Some(level_nodes.get(node_index - 1).ok_or(MerkleError::MissingLevel(level))?)
+            };
+            if let Some(sibling) = sibling {
+                proof.hashes.push(sibling.hash);
+            }
+            node_index /= 2;
+        }
+        Ok(proof)
     }
-}
 
-impl Default for Node {
-    fn default() -> Self {
-        Node{
-            hash: "".to_string(),
-            level: 0,
-            parent_index: (0, 0),
-            index: 0,
-            position: (0.0, 0.0),
-            start_position: (0.0, 0.0),
-            end_position: (0.0, 0.0),
-            is_hash: true
+    /// Check that the tree with root `old_root` and `old_size` leaves is a prefix of this one
+    pub fn validate_consistency(&self, old_root: &Digest, old_size: usize) -> Result<(), MerkleError> {
+        let root_hash = self.root_hash().ok_or(MerkleError::EmptyTree)?;
+        let proof = self.consistency_proof(old_size)?;
+        let (old_computed, new_computed) = proof::compute_consistency_roots(old_root, &proof, &self.hasher)?;
+        if old_computed != *old_root {
+            return Err(MerkleError::HashMismatch { expected: *old_root, found: old_computed });
+        }
+        if new_computed != root_hash {
+            return Err(MerkleError::HashMismatch { expected: root_hash, found: new_computed });
         }
+        Ok(())
     }
 }
 
-fn pad_words(words: Vec<&str>) -> Vec<&str> {
-    let mut padded_words = words.clone();
-    let len = padded_words.len();
+pub fn build_tree<L: Leaf>(leaves: impl IntoIterator<Item = L>) -> Result<MerkleTree, MerkleError> {
+    build_tree_with(leaves, Keccak256, TreeOptions::default())
+}
+
+/// Build a tree over any number of leaves, words or any other `Leaf`, with the given hasher,
+/// pairing odd nodes per `options.odd_rule`
+pub fn build_tree_with<L: Leaf, H: MerkleHasher>(leaves: impl IntoIterator<Item = L>, hasher: H, options: TreeOptions) -> Result<MerkleTree<H>, MerkleError> {
+    let mut leaves: Vec<(String, Vec<u8>, Digest)> = leaves.into_iter()
+        .map(|leaf| {
+            let value = leaf.encode().into_owned();
+            let hash = hasher::hash_leaf(&hasher, &value, &options);
+            (leaf.label(), value, hash)
+        })
+        .collect();
+    if leaves.is_empty() {
+        return Err(MerkleError::EmptyTree);
+    }
 
-    if len > 4 && len < 8 {
-        padded_words.resize(8, "<pad>");
-    } 
-    else if len > 8 && len < 16 {
-        padded_words.resize(16, "<pad>");
+    match options.leaf_order {
+        LeafOrder::AsGiven => {}
+        LeafOrder::ByHash => leaves.sort_by_key(|(_, _, hash)| *hash),
+        LeafOrder::ByValue => leaves.sort_by(|(_, a, _), (_, b, _)| a.cmp(b)),
     }
 
-    padded_words
+    let hashes: Vec<Digest> = leaves.iter().map(|(_, _, hash)| *hash).collect();
+    let (words, values) = leaves.into_iter().map(|(word, value, _)| (word, value)).unzip();
+    let mut tree = MerkleTree::new(&hashes, values, words, hasher, options);
+    tree.build();
+    Ok(tree)
+}
+
+/// Rebuild a saved tree from its leaves as they were, keeping their hashes and order
+pub(crate) fn restore_tree<H: MerkleHasher>(hashes: &[Digest], values: Vec<Vec<u8>>, words: Vec<String>, hasher: H, options: TreeOptions) -> Result<MerkleTree<H>, MerkleError> {
+    if hashes.is_empty() {
+        return Err(MerkleError::EmptyTree);
+    }
+
+    let mut tree = MerkleTree::new(hashes, values, words, hasher, options);
+    tree.build();
+    Ok(tree)
 }
 
-pub fn build_tree(words: Vec<&str>) -> MerkleTree {
-    let padded_words = pad_words(words);
+/// Build a tree whose leaves are already hashes, such as transaction ids. The leaves are taken as
+/// they are, not hashed again or sorted, and their hex stands in for the labels.
+pub fn build_tree_from_hashes<H: MerkleHasher>(hashes: &[Digest], hasher: H, options: TreeOptions) -> Result<MerkleTree<H>, MerkleError> {
+    if hashes.is_empty() {
+        return Err(MerkleError::EmptyTree);
+    }
 
-    let hashes = hasher::hash_words(padded_words.clone());
-    let mut tree = MerkleTree::new(&hashes,
-        padded_words.iter().map(|s| s.to_string()).collect());
+    let mut tree = MerkleTree::new(hashes, hashes.iter().map(|hash| hash.0.to_vec()).collect(),
+        hashes.iter().map(|hash| hash.to_hex()).collect(), hasher, options);
     tree.build();
-    return tree;
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> MerkleTree {
+        build_tree(["a", "b", "c", "d", "e"]).unwrap()
+    }
+
+    #[test]
+    fn proofs_go_stale_when_the_tree_changes() {
+        let mut tree = tree();
+        let proof = tree.inclusion_proof(1).unwrap();
+        assert_eq!(tree.validate_proof(&proof), Ok(()));
+
+        let old_root = tree.root_hash().unwrap();
+        tree.update(3, "x").unwrap();
+        let root = tree.root_hash().unwrap();
+        assert_eq!(tree.validate_proof(&proof), Err(MerkleError::StaleProof { proof_root: old_root, root }));
+
+        // updates drop the stored proof, a proof kept from before is stale
+        assert!(tree.proof.is_none());
+        tree.proof = Some(proof);
+        assert_eq!(tree.check_inclusion_proof(1), Verification::Stale);
+    }
+
+    #[test]
+    fn tampered_proofs_give_a_hash_mismatch() {
+        let mut tree = tree();
+        let mut proof = tree.inclusion_proof(1).unwrap();
+        proof.steps[0].sibling = Digest([7; 32]);
+        let root = tree.root_hash().unwrap();
+        assert!(matches!(tree.validate_proof(&proof), Err(MerkleError::HashMismatch { expected, .. }) if expected == root));
+
+        proof.steps[0].side = Side::Right;
+        assert_eq!(tree.validate_proof(&proof), Err(MerkleError::MalformedProof));
+
+        proof.leaf_index = 9;
+        assert_eq!(tree.validate_proof(&proof), Err(MerkleError::IndexOutOfRange { index: 9, size: 5 }));
+    }
+
+    #[test]
+    fn a_word_to_prove_that_differs_fails() {
+        let mut tree = tree();
+        tree.inclusion_proof(2).unwrap();
+        assert_eq!(tree.check_inclusion_proof(2), Verification::Valid);
+
+        let hash = hasher::hash_word(&tree.hasher, "z", &tree.options);
+        tree.word_to_prove = Some(WordToProve { index: 2, word: "z".to_string(), hash, display_hash: String::new() });
+        assert_eq!(tree.check_inclusion_proof(2), Verification::RootMismatch);
+        tree.multi_proof(&[0, 2]).unwrap();
+        assert_eq!(tree.check_multi_proof(), Verification::RootMismatch);
+    }
+
+    #[test]
+    fn tombstones_keep_the_other_leaves_in_place() {
+        let mut tree = tree();
+        tree.remove(1, RemovePolicy::Tombstone).unwrap();
+        let proof = tree.inclusion_proof(4).unwrap();
+        assert_eq!(proof.leaf_index, 4);
+        assert_eq!(tree.validate_proof(&proof), Ok(()));
+        assert_eq!(tree.leaf(1).unwrap().hash, hasher::EMPTY_HASH);
+    }
+
+    #[test]
+    fn exclusion_needs_sorted_leaves() {
+        let mut tree = tree();
+        assert_eq!(tree.exclusion_proof("z").unwrap_err(), MerkleError::Unsorted);
+
+        let mut sorted = build_tree_with(["c", "a", "e"], Keccak256, TreeOptions { leaf_order: LeafOrder::ByValue, ..Default::default() }).unwrap();
+        assert_eq!(sorted.exclusion_proof("a").unwrap_err(), MerkleError::WordIncluded { index: 0 });
+        assert!(sorted.exclusion_proof("b").unwrap().is_valid);
+        assert_eq!(sorted.check_exclusion_proof("b"), Verification::Valid);
+    }
+
+    /// Every node as (level, index, hash, parent index), in order
+    fn node_list(tree: &MerkleTree) -> Vec<(u32, usize, Digest, (u32, u32))> {
+        let mut nodes: Vec<_> = tree.nodes.values().flatten()
+            .map(|node| (node.level, node.index, node.hash, node.parent_index))
+            .collect();
+        nodes.sort_by_key(|node| (node.0, node.1));
+        nodes
+    }
+
+    #[test]
+    fn incremental_changes_match_a_full_rebuild() {
+        let words: Vec<String> = (0..40).map(|i| format!("word {}", i)).collect();
+        for odd_rule in [OddNodeRule::Duplicate, OddNodeRule::Promote, OddNodeRule::PadEmpty] {
+            for sorted_pairs in [false, true] {
+                for domain_separation in [false, true] {
+                    let options = TreeOptions { odd_rule, sorted_pairs, domain_separation, ..Default::default() };
+                    let mut pushed = build_tree_with(&words[..1], Keccak256, options).unwrap();
+                    for size in 1..=words.len() {
+                        if size > 1 {
+                            assert_eq!(pushed.push(&words[size - 1]).unwrap(), size - 1);
+                        }
+                        let built = build_tree_with(&words[..size], Keccak256, options).unwrap();
+                        assert_eq!(pushed.levels, built.levels, "{:?} size {}", options, size);
+                        assert_eq!(pushed.root_hash(), built.root_hash(), "{:?} size {}", options, size);
+                        assert_eq!(node_list(&pushed), node_list(&built), "{:?} size {}", options, size);
+
+                        // changing the last leaf and one in the middle
+                        let mut updated = built.clone();
+                        let mut changed = words[..size].to_vec();
+                        for index in [size - 1, size / 2] {
+                            updated.update(index, "changed").unwrap();
+                            changed[index] = "changed".to_string();
+                        }
+                        let rebuilt = build_tree_with(&changed, Keccak256, options).unwrap();
+                        assert_eq!(updated.root_hash(), rebuilt.root_hash(), "{:?} size {}", options, size);
+                        assert_eq!(node_list(&updated), node_list(&rebuilt), "{:?} size {}", options, size);
+                    }
+                }
+            }
+        }
+    }
 }